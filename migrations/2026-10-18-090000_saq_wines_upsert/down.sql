@@ -0,0 +1,3 @@
+ALTER TABLE saq_wines DROP COLUMN last_seen_at;
+ALTER TABLE saq_wines DROP COLUMN delisted;
+ALTER TABLE saq_wines DROP COLUMN saq_code;
@@ -0,0 +1,3 @@
+ALTER TABLE saq_wines ADD COLUMN saq_code varchar UNIQUE;
+ALTER TABLE saq_wines ADD COLUMN delisted boolean NOT NULL DEFAULT false;
+ALTER TABLE saq_wines ADD COLUMN last_seen_at timestamp NOT NULL DEFAULT now();
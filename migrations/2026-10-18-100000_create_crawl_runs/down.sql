@@ -0,0 +1,5 @@
+DROP INDEX crawl_failures_crawl_run_id_idx;
+DROP TABLE crawl_failures;
+DROP INDEX crawl_runs_started_at_idx;
+DROP TABLE crawl_runs;
+DROP TYPE crawl_status;
@@ -0,0 +1,24 @@
+CREATE TYPE crawl_status AS ENUM ('running', 'succeeded', 'failed');
+CREATE TABLE crawl_runs
+(
+    id SERIAL PRIMARY KEY,
+    status crawl_status NOT NULL DEFAULT 'running',
+    started_at timestamp NOT NULL DEFAULT now(),
+    finished_at timestamp,
+    pages_visited integer NOT NULL DEFAULT 0,
+    wines_added integer NOT NULL DEFAULT 0,
+    wines_updated integer NOT NULL DEFAULT 0,
+    wines_failed integer NOT NULL DEFAULT 0,
+    error varchar
+);
+CREATE INDEX crawl_runs_started_at_idx ON crawl_runs (started_at);
+
+CREATE TABLE crawl_failures
+(
+    id SERIAL PRIMARY KEY,
+    crawl_run_id integer NOT NULL REFERENCES crawl_runs (id) ON DELETE CASCADE,
+    url varchar NOT NULL,
+    message varchar NOT NULL,
+    created_at timestamp NOT NULL DEFAULT now()
+);
+CREATE INDEX crawl_failures_crawl_run_id_idx ON crawl_failures (crawl_run_id);
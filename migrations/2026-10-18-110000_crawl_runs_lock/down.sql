@@ -0,0 +1,9 @@
+DROP INDEX crawl_runs_single_running_idx;
+ALTER TABLE crawl_runs DROP COLUMN cancel_requested;
+UPDATE crawl_runs SET status = 'failed' WHERE status = 'cancelled';
+ALTER TYPE crawl_status RENAME TO crawl_status_old;
+CREATE TYPE crawl_status AS ENUM ('running', 'succeeded', 'failed');
+ALTER TABLE crawl_runs ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE crawl_runs ALTER COLUMN status TYPE crawl_status USING status::text::crawl_status;
+ALTER TABLE crawl_runs ALTER COLUMN status SET DEFAULT 'running';
+DROP TYPE crawl_status_old;
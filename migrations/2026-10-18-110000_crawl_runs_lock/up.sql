@@ -0,0 +1,11 @@
+-- the type is recreated rather than altered, ALTER TYPE ... ADD VALUE can't run in the
+-- transaction of a migration before PostgreSQL 12.
+ALTER TYPE crawl_status RENAME TO crawl_status_old;
+CREATE TYPE crawl_status AS ENUM ('running', 'succeeded', 'failed', 'cancelled');
+ALTER TABLE crawl_runs ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE crawl_runs ALTER COLUMN status TYPE crawl_status USING status::text::crawl_status;
+ALTER TABLE crawl_runs ALTER COLUMN status SET DEFAULT 'running';
+DROP TYPE crawl_status_old;
+ALTER TABLE crawl_runs ADD COLUMN cancel_requested boolean NOT NULL DEFAULT false;
+-- only one crawl may be running at any given time
+CREATE UNIQUE INDEX crawl_runs_single_running_idx ON crawl_runs (status) WHERE status = 'running';
@@ -0,0 +1,2 @@
+DROP INDEX saq_wine_prices_saq_wine_id_recorded_at_idx;
+DROP TABLE saq_wine_prices;
@@ -0,0 +1,10 @@
+CREATE TABLE saq_wine_prices
+(
+    id SERIAL PRIMARY KEY,
+    saq_wine_id integer NOT NULL REFERENCES saq_wines (id) ON DELETE CASCADE,
+    price decimal NOT NULL,
+    recorded_at timestamp NOT NULL DEFAULT now()
+);
+CREATE INDEX saq_wine_prices_saq_wine_id_recorded_at_idx ON saq_wine_prices (saq_wine_id, recorded_at);
+-- start the history with the prices we already know
+INSERT INTO saq_wine_prices (saq_wine_id, price, recorded_at) SELECT id, price, last_seen_at FROM saq_wines;
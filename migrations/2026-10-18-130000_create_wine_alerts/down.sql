@@ -0,0 +1,6 @@
+DROP INDEX notifications_pending_idx;
+DROP INDEX notifications_user_id_idx;
+DROP TABLE notifications;
+DROP INDEX wine_alerts_user_id_idx;
+DROP TABLE wine_alerts;
+DROP TYPE alert_kind;
@@ -0,0 +1,27 @@
+CREATE TYPE alert_kind AS ENUM ('price_threshold', 'price_drop', 'restock');
+CREATE TABLE wine_alerts
+(
+    id SERIAL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind alert_kind NOT NULL,
+    -- price of a 750 ml bottle, only used by price_threshold alerts
+    max_price decimal CHECK (kind <> 'price_threshold' OR max_price IS NOT NULL),
+    -- the alert covers every recommendation of the user when null
+    wine_recommendation_id integer REFERENCES wine_recommendations (id) ON DELETE CASCADE,
+    created_at timestamp NOT NULL DEFAULT now()
+);
+CREATE INDEX wine_alerts_user_id_idx ON wine_alerts (user_id);
+
+CREATE TABLE notifications
+(
+    id SERIAL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    wine_alert_id integer REFERENCES wine_alerts (id) ON DELETE SET NULL,
+    saq_wine_id integer NOT NULL REFERENCES saq_wines (id) ON DELETE CASCADE,
+    message varchar NOT NULL,
+    created_at timestamp NOT NULL DEFAULT now(),
+    delivered_at timestamp,
+    read_at timestamp
+);
+CREATE INDEX notifications_user_id_idx ON notifications (user_id);
+CREATE INDEX notifications_pending_idx ON notifications (id) WHERE delivered_at IS NULL;
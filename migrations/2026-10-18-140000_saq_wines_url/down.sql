@@ -0,0 +1 @@
+ALTER TABLE saq_wines DROP COLUMN url;
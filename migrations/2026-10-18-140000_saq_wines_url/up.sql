@@ -0,0 +1,2 @@
+-- product page the wine was crawled from, unknown for wines saved before it was recorded
+ALTER TABLE saq_wines ADD COLUMN url VARCHAR;
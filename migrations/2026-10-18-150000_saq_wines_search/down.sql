@@ -0,0 +1,9 @@
+DROP INDEX saq_wines_search_text_trgm_idx;
+DROP INDEX saq_wines_search_vector_idx;
+DROP FUNCTION saq_wine_search_text(text, text);
+DROP FUNCTION saq_wine_search_query(text);
+DROP FUNCTION saq_wine_search_vector(text, text, text, text[]);
+DROP FUNCTION normalize_search_text(text);
+DROP FUNCTION immutable_unaccent(text);
+DROP EXTENSION IF EXISTS pg_trgm;
+DROP EXTENSION IF EXISTS unaccent;
@@ -0,0 +1,33 @@
+CREATE EXTENSION IF NOT EXISTS unaccent;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- unaccent is only STABLE because its dictionary could change, indexes need an IMMUTABLE function
+CREATE FUNCTION immutable_unaccent(text) RETURNS text AS $$
+    SELECT public.unaccent('public.unaccent', $1)
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+-- lowercase and without accents so "cotes du rhone" matches "Côtes du Rhône"
+CREATE FUNCTION normalize_search_text(text) RETURNS text AS $$
+    SELECT lower(immutable_unaccent($1))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+CREATE FUNCTION saq_wine_search_vector(name text, producer text, region text, grape_varieties text[])
+RETURNS tsvector AS $$
+    SELECT setweight(to_tsvector('simple', normalize_search_text(coalesce(name, ''))), 'A')
+        || setweight(to_tsvector('simple', normalize_search_text(coalesce(producer, ''))), 'B')
+        || setweight(to_tsvector('simple', normalize_search_text(coalesce(region, ''))), 'C')
+        || setweight(to_tsvector('simple', normalize_search_text(coalesce(array_to_string(grape_varieties, ' '), ''))), 'C')
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+CREATE FUNCTION saq_wine_search_query(text) RETURNS tsquery AS $$
+    SELECT plainto_tsquery('simple', normalize_search_text($1))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+CREATE FUNCTION saq_wine_search_text(name text, producer text) RETURNS text AS $$
+    SELECT normalize_search_text(coalesce(name, '') || ' ' || coalesce(producer, ''))
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+CREATE INDEX saq_wines_search_vector_idx ON saq_wines
+    USING GIN (saq_wine_search_vector(name, producer, region, grape_varieties));
+CREATE INDEX saq_wines_search_text_trgm_idx ON saq_wines
+    USING GIN (saq_wine_search_text(name, producer) gin_trgm_ops);
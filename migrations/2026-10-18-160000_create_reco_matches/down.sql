@@ -0,0 +1,2 @@
+DROP INDEX wine_recommendations_user_id_idx;
+DROP TABLE reco_matches;
@@ -0,0 +1,24 @@
+-- which wines match which recommendation, recomputed by the application when a crawl
+-- finishes or a recommendation changes instead of joining with ILIKE on every request.
+CREATE TABLE reco_matches
+(
+    wine_recommendation_id integer NOT NULL REFERENCES wine_recommendations (id) ON DELETE CASCADE,
+    saq_wine_id integer NOT NULL REFERENCES saq_wines (id) ON DELETE CASCADE,
+    PRIMARY KEY (wine_recommendation_id, saq_wine_id)
+);
+CREATE INDEX reco_matches_saq_wine_id_idx ON reco_matches (saq_wine_id);
+CREATE INDEX wine_recommendations_user_id_idx ON wine_recommendations (user_id);
+
+INSERT INTO reco_matches (wine_recommendation_id, saq_wine_id)
+SELECT wine_recommendations.id, saq_wines.id
+FROM saq_wines
+INNER JOIN wine_recommendations ON
+    (wine_recommendations.country = '' OR saq_wines.country ILIKE wine_recommendations.country)
+    AND (wine_recommendations.region = '' OR saq_wines.region ILIKE wine_recommendations.region)
+    AND (wine_recommendations.designation_of_origin = ''
+        OR saq_wines.designation_of_origin ILIKE wine_recommendations.designation_of_origin || '%')
+    AND (wine_recommendations.wine_name = '' OR saq_wines.name ILIKE wine_recommendations.wine_name || '%')
+    AND (wine_recommendations.producer = '' OR saq_wines.producer ILIKE wine_recommendations.producer)
+    AND (wine_recommendations.grape_variety = ''
+        OR wine_recommendations.grape_variety ILIKE ANY (saq_wines.grape_varieties))
+    AND saq_wines.color = wine_recommendations.color;
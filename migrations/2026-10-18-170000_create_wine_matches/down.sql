@@ -0,0 +1 @@
+DROP VIEW wine_matches;
@@ -0,0 +1,38 @@
+-- one row per user and matched wine, rated by its best recommendation and listing every
+-- recommendation it matched. Rows without a user_id cover the recommendations of everyone,
+-- which is what visitors who are not logged in see.
+CREATE VIEW wine_matches AS
+SELECT * FROM (
+    SELECT DISTINCT ON (wine_recommendations.user_id, reco_matches.saq_wine_id)
+        wine_recommendations.user_id,
+        reco_matches.saq_wine_id,
+        wine_recommendations.rating,
+        array_agg(wine_recommendations.id) OVER (
+            PARTITION BY wine_recommendations.user_id, reco_matches.saq_wine_id
+            ORDER BY wine_recommendations.rating DESC, wine_recommendations.id
+            ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING
+        ) AS wine_recommendation_ids
+    FROM reco_matches
+    INNER JOIN wine_recommendations ON wine_recommendations.id = reco_matches.wine_recommendation_id
+    WHERE wine_recommendations.user_id IS NOT NULL
+    ORDER BY wine_recommendations.user_id, reco_matches.saq_wine_id,
+        wine_recommendations.rating DESC, wine_recommendations.id
+) AS user_matches
+UNION ALL
+-- user_id is a column of the DISTINCT ON so filtering on it skips this part entirely
+SELECT * FROM (
+    SELECT DISTINCT ON (everyone.user_id, reco_matches.saq_wine_id)
+        everyone.user_id,
+        reco_matches.saq_wine_id,
+        wine_recommendations.rating,
+        array_agg(wine_recommendations.id) OVER (
+            PARTITION BY everyone.user_id, reco_matches.saq_wine_id
+            ORDER BY wine_recommendations.rating DESC, wine_recommendations.id
+            ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING
+        ) AS wine_recommendation_ids
+    FROM (SELECT NULL::integer AS user_id) AS everyone
+    CROSS JOIN reco_matches
+    INNER JOIN wine_recommendations ON wine_recommendations.id = reco_matches.wine_recommendation_id
+    ORDER BY everyone.user_id, reco_matches.saq_wine_id,
+        wine_recommendations.rating DESC, wine_recommendations.id
+) AS everyone_matches;
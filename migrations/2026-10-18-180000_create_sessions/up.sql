@@ -0,0 +1,12 @@
+CREATE TABLE sessions
+(
+    id SERIAL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- sha256 of the token kept in the cookie, the token itself is never stored
+    token_hash bytea NOT NULL UNIQUE,
+    user_agent varchar,
+    created_at timestamp NOT NULL DEFAULT now(),
+    last_seen_at timestamp NOT NULL DEFAULT now(),
+    expires_at timestamp NOT NULL
+);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
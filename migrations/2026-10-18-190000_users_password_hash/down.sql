@@ -0,0 +1,6 @@
+ALTER TABLE users DROP CONSTRAINT users_password_check;
+-- users migrated to password_hash have no legacy hash to go back to and will need a reset
+UPDATE users SET salt = '', password = '' WHERE salt IS NULL OR password IS NULL;
+ALTER TABLE users ALTER COLUMN salt SET NOT NULL;
+ALTER TABLE users ALTER COLUMN password SET NOT NULL;
+ALTER TABLE users DROP COLUMN password_hash;
@@ -0,0 +1,7 @@
+-- PHC string, e.g. $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>
+ALTER TABLE users ADD COLUMN password_hash varchar;
+-- salt and password hold the legacy argon2i hashes until the user logs in again
+ALTER TABLE users ALTER COLUMN salt DROP NOT NULL;
+ALTER TABLE users ALTER COLUMN password DROP NOT NULL;
+ALTER TABLE users ADD CONSTRAINT users_password_check
+    CHECK (password_hash IS NOT NULL OR (salt IS NOT NULL AND password IS NOT NULL));
@@ -0,0 +1,17 @@
+-- users registered before sign-up existed were created by an admin
+ALTER TABLE users ADD COLUMN email_verified_at timestamp;
+UPDATE users SET email_verified_at = now();
+
+CREATE TYPE token_purpose AS ENUM ('email_verification', 'password_reset');
+CREATE TABLE user_tokens
+(
+    id SERIAL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    purpose token_purpose NOT NULL,
+    -- sha256 of the token sent by email, like sessions
+    token_hash bytea NOT NULL UNIQUE,
+    created_at timestamp NOT NULL DEFAULT now(),
+    expires_at timestamp NOT NULL,
+    used_at timestamp
+);
+CREATE INDEX user_tokens_user_id_idx ON user_tokens (user_id);
@@ -0,0 +1,14 @@
+CREATE TYPE api_scope AS ENUM ('crawl', 'users');
+CREATE TABLE api_tokens
+(
+    id SERIAL PRIMARY KEY,
+    -- the admin who created it, the token stops working when they aren't one anymore
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name varchar NOT NULL,
+    scopes api_scope[] NOT NULL,
+    -- sha256 of the token sent as Authorization: Bearer <token>
+    token_hash bytea NOT NULL UNIQUE,
+    created_at timestamp NOT NULL DEFAULT now(),
+    last_used_at timestamp
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
@@ -0,0 +1,3 @@
+-- emails are compared lowercased, accounts differing only by case must be merged by hand first
+UPDATE users SET email = lower(trim(email));
+ALTER TABLE users ADD CONSTRAINT users_email_lowercase_check CHECK (email = lower(trim(email)));
@@ -0,0 +1,101 @@
+-- Compares the ILIKE join GET /wines/ used to run on every request with the reco_matches
+-- lookup that replaced it, on a catalogue about the size of saq.com.
+--
+--   psql "$DATABASE_URL" -f scripts/bench_reco_matches.sql
+--
+-- Runs in a transaction that is rolled back, the database is left untouched.
+\timing off
+BEGIN;
+
+INSERT INTO users (email, admin, salt, password)
+SELECT 'bench' || n || '@example.com', false, '\x00', '\x00'
+FROM generate_series(1, 500) n;
+
+INSERT INTO saq_wines (saq_code, name, country, region, designation_of_origin,
+    regulated_designation, producer, volume, price, alcohol_percent, color,
+    grape_varieties, available_online)
+SELECT 'bench' || n,
+    'Domaine ' || (n % 3000) || ' Cuvée ' || n,
+    (ARRAY['France', 'Italy', 'Spain', 'Portugal', 'Chile', 'Argentina', 'United States', 'Australia'])[n % 8 + 1],
+    'Region ' || (n % 120),
+    'Designation ' || (n % 400),
+    n % 3 <> 0,
+    'Producer ' || (n % 3000),
+    (ARRAY[375, 750, 750, 750, 1500])[n % 5 + 1],
+    10 + (n % 9000) / 100.0,
+    11 + (n % 40) / 10.0,
+    (ARRAY['red', 'white', 'pink'])[n % 3 + 1]::wine_color,
+    ARRAY[(ARRAY['Syrah', 'Grenache', 'Merlot', 'Pinot noir', 'Chardonnay', 'Riesling', 'Malbec', 'Tempranillo'])[n % 8 + 1],
+          (ARRAY['Cinsault', 'Mourvèdre', 'Cabernet sauvignon', 'Sauvignon blanc', 'Gamay'])[n % 5 + 1]],
+    n % 4 <> 0
+FROM generate_series(1, 15000) n;
+
+-- 10 recommendations per user, each narrowing down the country with one more criterion
+INSERT INTO wine_recommendations (country, region, designation_of_origin, producer, rating,
+    color, grape_variety, user_id, wine_name)
+SELECT
+    (ARRAY['France', 'Italy', 'Spain', 'Portugal', 'Chile', 'Argentina', 'United States', 'Australia'])[(users.id + n) % 8 + 1],
+    CASE WHEN n % 5 = 0 THEN 'Region ' || ((users.id * n) % 120) ELSE '' END,
+    CASE WHEN n % 5 = 1 THEN 'Designation ' || ((users.id * n) % 40) ELSE '' END,
+    CASE WHEN n % 5 = 2 THEN 'Producer ' || ((users.id * n) % 3000) ELSE '' END,
+    n % 5 + 1,
+    (ARRAY['red', 'white', 'pink'])[n % 3 + 1]::wine_color,
+    CASE WHEN n % 5 = 3 THEN (ARRAY['syrah', 'merlot', 'riesling', 'malbec'])[(users.id + n) % 4 + 1] ELSE '' END,
+    users.id,
+    CASE WHEN n % 5 = 4 THEN 'Domaine ' || ((users.id * n) % 3000) ELSE '' END
+FROM users, generate_series(1, 10) n
+WHERE users.email LIKE 'bench%';
+
+ANALYZE users;
+ANALYZE saq_wines;
+ANALYZE wine_recommendations;
+
+\echo '== refreshing reco_matches after a crawl (all recommendations)'
+\timing on
+DELETE FROM reco_matches;
+INSERT INTO reco_matches (wine_recommendation_id, saq_wine_id)
+SELECT wine_recommendations.id, saq_wines.id
+FROM saq_wines
+INNER JOIN wine_recommendations ON
+    (wine_recommendations.country = '' OR saq_wines.country ILIKE wine_recommendations.country)
+    AND (wine_recommendations.region = '' OR saq_wines.region ILIKE wine_recommendations.region)
+    AND (wine_recommendations.designation_of_origin = ''
+        OR saq_wines.designation_of_origin ILIKE wine_recommendations.designation_of_origin || '%')
+    AND (wine_recommendations.wine_name = '' OR saq_wines.name ILIKE wine_recommendations.wine_name || '%')
+    AND (wine_recommendations.producer = '' OR saq_wines.producer ILIKE wine_recommendations.producer)
+    AND (wine_recommendations.grape_variety = ''
+        OR wine_recommendations.grape_variety ILIKE ANY (saq_wines.grape_varieties))
+    AND saq_wines.color = wine_recommendations.color;
+\timing off
+ANALYZE reco_matches;
+SELECT count(*) AS reco_matches FROM reco_matches;
+
+SELECT id AS bench_user_id FROM users WHERE email = 'bench250@example.com' \gset
+
+\echo '== GET /wines/ for one user, ILIKE join (before)'
+EXPLAIN (ANALYZE, COSTS OFF, SUMMARY ON)
+SELECT saq_wines.id, saq_wines.name, wine_recommendations.rating
+FROM saq_wines
+INNER JOIN wine_recommendations ON
+    (wine_recommendations.country = '' OR saq_wines.country ILIKE wine_recommendations.country)
+    AND (wine_recommendations.region = '' OR saq_wines.region ILIKE wine_recommendations.region)
+    AND (wine_recommendations.designation_of_origin = ''
+        OR saq_wines.designation_of_origin ILIKE wine_recommendations.designation_of_origin || '%')
+    AND (wine_recommendations.wine_name = '' OR saq_wines.name ILIKE wine_recommendations.wine_name || '%')
+    AND (wine_recommendations.producer = '' OR saq_wines.producer ILIKE wine_recommendations.producer)
+    AND (wine_recommendations.grape_variety = ''
+        OR wine_recommendations.grape_variety ILIKE ANY (saq_wines.grape_varieties))
+    AND saq_wines.color = wine_recommendations.color
+WHERE saq_wines.delisted = false AND wine_recommendations.user_id = :bench_user_id
+ORDER BY saq_wines.price / saq_wines.volume, saq_wines.id;
+
+\echo '== GET /wines/ for one user, reco_matches lookup (after)'
+EXPLAIN (ANALYZE, COSTS OFF, SUMMARY ON)
+SELECT saq_wines.id, saq_wines.name, wine_recommendations.rating
+FROM reco_matches
+INNER JOIN saq_wines ON saq_wines.id = reco_matches.saq_wine_id
+INNER JOIN wine_recommendations ON wine_recommendations.id = reco_matches.wine_recommendation_id
+WHERE saq_wines.delisted = false AND wine_recommendations.user_id = :bench_user_id
+ORDER BY saq_wines.price / saq_wines.volume, saq_wines.id;
+
+ROLLBACK;
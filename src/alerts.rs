@@ -0,0 +1,330 @@
+use crate::models::{create_notification, NewNotification, SaqWine, WineAlert};
+use crate::schema::{reco_matches, saq_wines, wine_alerts, wine_recommendations};
+use crate::types::AlertKindEnum;
+use bigdecimal::BigDecimal;
+use diesel::pg::expression::dsl::any;
+use diesel::pg::PgConnection;
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use std::collections::{HashMap, HashSet};
+
+// what a crawl changed on a wine we already knew about.
+pub struct SaqWineChange {
+    pub saq_wine_id: i32,
+    pub previous_price: BigDecimal,
+    pub previous_available_online: bool,
+}
+
+impl SaqWineChange {
+    pub fn between(previous_saq_wine: &SaqWine, saq_wine: &SaqWine) -> Option<SaqWineChange> {
+        if previous_saq_wine.price == saq_wine.price
+            && previous_saq_wine.available_online == saq_wine.available_online
+        {
+            return None;
+        }
+        Some(SaqWineChange {
+            saq_wine_id: saq_wine.id,
+            previous_price: previous_saq_wine.price.clone(),
+            previous_available_online: previous_saq_wine.available_online,
+        })
+    }
+}
+
+// alert prices are for a 750 ml bottle
+fn bottle_price(price: &BigDecimal, volume: &BigDecimal) -> BigDecimal {
+    if *volume == BigDecimal::from(0) {
+        return price.clone();
+    }
+    price.clone() * BigDecimal::from(750) / volume.clone()
+}
+
+fn alert_message(alert: &WineAlert, saq_wine: &SaqWine, change: &SaqWineChange) -> Option<String> {
+    match alert.kind {
+        AlertKindEnum::PriceDrop if saq_wine.price < change.previous_price => Some(format!(
+            "{} dropped to ${} (was ${}).",
+            saq_wine.name, saq_wine.price, change.previous_price
+        )),
+        AlertKindEnum::PriceThreshold => {
+            let max_price = alert.max_price.as_ref()?;
+            // only notify when the price goes under the threshold, not on every crawl after that
+            let crossed = bottle_price(&saq_wine.price, &saq_wine.volume) <= *max_price
+                && bottle_price(&change.previous_price, &saq_wine.volume) > *max_price;
+            if !crossed {
+                return None;
+            }
+            Some(format!(
+                "{} is now ${}, under your ${} limit.",
+                saq_wine.name, saq_wine.price, max_price
+            ))
+        }
+        AlertKindEnum::Restock
+            if saq_wine.available_online && !change.previous_available_online =>
+        {
+            Some(format!("{} is available online again.", saq_wine.name))
+        }
+        _ => None,
+    }
+}
+
+// queues a notification for every alert triggered by the changes of a crawl.
+pub fn evaluate_wine_alerts(conn: &PgConnection, changes: &[SaqWineChange]) -> QueryResult<usize> {
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    let changes: HashMap<i32, &SaqWineChange> = changes
+        .iter()
+        .map(|change| (change.saq_wine_id, change))
+        .collect();
+    let saq_wine_ids: Vec<i32> = changes.keys().cloned().collect();
+    let changed_saq_wines = saq_wines::table
+        .filter(saq_wines::id.eq(any(&saq_wine_ids)))
+        .load::<SaqWine>(conn)?;
+    // reco_matches must be refreshed first
+    let matches: Vec<(i32, i32, Option<i32>)> = reco_matches::table
+        .inner_join(wine_recommendations::table)
+        .filter(reco_matches::saq_wine_id.eq(any(&saq_wine_ids)))
+        .select((
+            reco_matches::saq_wine_id,
+            reco_matches::wine_recommendation_id,
+            wine_recommendations::user_id,
+        ))
+        .load(conn)?;
+    // saq_wine_id -> user_id -> ids of the user's recommendations matching the wine
+    let mut matched: HashMap<i32, HashMap<i32, HashSet<i32>>> = HashMap::new();
+    for (saq_wine_id, wine_recommendation_id, user_id) in matches {
+        if let Some(user_id) = user_id {
+            matched
+                .entry(saq_wine_id)
+                .or_default()
+                .entry(user_id)
+                .or_default()
+                .insert(wine_recommendation_id);
+        }
+    }
+    // alerts only cover wines matching the user's recommendations
+    let user_ids: HashSet<i32> = matched
+        .values()
+        .flat_map(|users| users.keys().cloned())
+        .collect();
+    let mut alerts: HashMap<i32, Vec<WineAlert>> = HashMap::new();
+    for alert in wine_alerts::table
+        .filter(wine_alerts::user_id.eq(any(user_ids.into_iter().collect::<Vec<i32>>())))
+        .load::<WineAlert>(conn)?
+    {
+        alerts.entry(alert.user_id).or_default().push(alert);
+    }
+
+    let mut notified = 0;
+    for saq_wine in &changed_saq_wines {
+        let change = changes[&saq_wine.id];
+        let users = match matched.get(&saq_wine.id) {
+            Some(users) => users,
+            None => continue,
+        };
+        for (user_id, wine_recommendation_ids) in users {
+            for alert in alerts.get(user_id).into_iter().flatten() {
+                let recommendation_matched = alert
+                    .wine_recommendation_id
+                    .map_or(true, |id| wine_recommendation_ids.contains(&id));
+                if !recommendation_matched {
+                    continue;
+                }
+                if let Some(message) = alert_message(alert, saq_wine, change) {
+                    create_notification(
+                        conn,
+                        &NewNotification {
+                            user_id: alert.user_id,
+                            wine_alert_id: Some(alert.id),
+                            saq_wine_id: saq_wine.id,
+                            message: &message,
+                        },
+                    )?;
+                    notified += 1;
+                }
+            }
+        }
+    }
+    Ok(notified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::WineColorEnum;
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn saq_wine(price: &str, volume: &str, available_online: bool) -> SaqWine {
+        SaqWine {
+            id: 1,
+            name: "Château Test".to_string(),
+            country: "France".to_string(),
+            region: "Bordeaux".to_string(),
+            designation_of_origin: "".to_string(),
+            regulated_designation: false,
+            producer: "".to_string(),
+            volume: decimal(volume),
+            price: decimal(price),
+            alcohol_percent: decimal("13.5"),
+            color: WineColorEnum::Red,
+            grape_varieties: vec![],
+            available_online: available_online,
+            saq_code: Some("10324623".to_string()),
+            delisted: false,
+            last_seen_at: NaiveDate::from_ymd(2026, 10, 18).and_hms(3, 0, 0),
+            url: None,
+        }
+    }
+
+    fn wine_alert(kind: AlertKindEnum, max_price: Option<&str>) -> WineAlert {
+        WineAlert {
+            id: 1,
+            user_id: 1,
+            kind: kind,
+            max_price: max_price.map(decimal),
+            wine_recommendation_id: None,
+            created_at: NaiveDate::from_ymd(2026, 10, 18).and_hms(3, 0, 0),
+        }
+    }
+
+    fn change(previous_price: &str, previous_available_online: bool) -> SaqWineChange {
+        SaqWineChange {
+            saq_wine_id: 1,
+            previous_price: decimal(previous_price),
+            previous_available_online: previous_available_online,
+        }
+    }
+
+    #[test]
+    fn test_saq_wine_change_between() {
+        let previous_saq_wine = saq_wine("20.00", "750", true);
+        assert!(
+            SaqWineChange::between(&previous_saq_wine, &saq_wine("20.00", "750", true)).is_none()
+        );
+
+        let change =
+            SaqWineChange::between(&previous_saq_wine, &saq_wine("18.00", "750", true)).unwrap();
+        assert_eq!(change.previous_price, decimal("20.00"));
+        assert!(change.previous_available_online);
+
+        let change =
+            SaqWineChange::between(&previous_saq_wine, &saq_wine("20.00", "750", false)).unwrap();
+        assert_eq!(change.previous_price, decimal("20.00"));
+    }
+
+    #[test]
+    fn test_price_drop() {
+        let alert = wine_alert(AlertKindEnum::PriceDrop, None);
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("18.00", "750", true),
+                &change("20.00", true)
+            ),
+            Some("Château Test dropped to $18.00 (was $20.00).".to_string())
+        );
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("22.00", "750", true),
+                &change("20.00", true)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_price_threshold() {
+        let alert = wine_alert(AlertKindEnum::PriceThreshold, Some("19.00"));
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("18.00", "750", true),
+                &change("20.00", true)
+            ),
+            Some("Château Test is now $18.00, under your $19.00 limit.".to_string())
+        );
+        // already under the threshold at the previous crawl, the user was notified then
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("17.00", "750", true),
+                &change("18.00", true)
+            ),
+            None
+        );
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("19.50", "750", true),
+                &change("20.00", true)
+            ),
+            None
+        );
+        assert_eq!(
+            alert_message(
+                &wine_alert(AlertKindEnum::PriceThreshold, None),
+                &saq_wine("18.00", "750", true),
+                &change("20.00", true)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_price_threshold_bottle_price() {
+        // $36 for a magnum is $18 for 750 ml
+        let alert = wine_alert(AlertKindEnum::PriceThreshold, Some("19.00"));
+        assert!(alert_message(
+            &alert,
+            &saq_wine("36.00", "1500", true),
+            &change("40.00", true)
+        )
+        .is_some());
+        // $18 for a half bottle is $36 for 750 ml
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("18.00", "375", true),
+                &change("20.00", true)
+            ),
+            None
+        );
+        assert_eq!(
+            bottle_price(&decimal("18.00"), &decimal("0")),
+            decimal("18.00")
+        );
+    }
+
+    #[test]
+    fn test_restock() {
+        let alert = wine_alert(AlertKindEnum::Restock, None);
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("20.00", "750", true),
+                &change("20.00", false)
+            ),
+            Some("Château Test is available online again.".to_string())
+        );
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("20.00", "750", false),
+                &change("20.00", true)
+            ),
+            None
+        );
+        assert_eq!(
+            alert_message(
+                &alert,
+                &saq_wine("18.00", "750", true),
+                &change("20.00", true)
+            ),
+            None
+        );
+    }
+}
@@ -1,78 +1,386 @@
-use crate::crawler::crawl_saq;
-use crate::errors::LoginError;
-use crate::establish_connection;
+use crate::crawler::start_crawl;
+use crate::db::{self, DbPool};
+use crate::errors::{ApiError, LoginError};
+use crate::mailer::{base_url, send_in_background, Email};
 use crate::models::{
-    compute_salt, create_user, create_wine_recommendation, hash_password, NewWineRecommendation,
-    User, WineRecommendation,
+    create_api_token, create_session, create_user, create_user_token, create_wine_alert,
+    create_wine_recommendation, discard_user_tokens, find_api_token, find_session,
+    get_running_crawl_run, refresh_wine_recommendation_matches, request_crawl_run_cancel,
+    revoke_other_sessions, revoke_session, revoke_user_sessions, update_password_hash,
+    use_user_token, verify_user_email, ApiToken, CrawlFailure, CrawlRun, NewWineAlert,
+    NewWineRecommendation, Notification, SaqWine, SaqWinePrice, Session, User, WineAlert,
+    WineRecommendation,
 };
-use crate::schema::{saq_wines as saq, users, wine_recommendations as recos};
-use crate::types::WineColorEnum;
-use crate::utils::is_dup_wine;
+use crate::passwords::{dummy_password_check, hash_password, verify_password, PasswordCheck};
+use crate::schema::{
+    api_tokens, crawl_failures, crawl_runs, notifications, reco_matches, saq_wine_prices,
+    saq_wines as saq, sessions, users, wine_alerts, wine_recommendations as recos,
+};
+use crate::search::{saq_wine_search, saq_wine_search_rank};
+use crate::types::{AlertKindEnum, ApiScopeEnum, TokenPurposeEnum, WineColorEnum};
+use crate::utils::{env_or, escape_like, next_page_link};
+use crate::validation::{normalize_email, RatingScale, Validator};
+use crate::views::wine_matches;
 use actix_files::NamedFile;
 use actix_identity::Identity;
 use actix_web::http::header::ContentType;
-use actix_web::http::header::AUTHORIZATION;
-use actix_web::{error, http, web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use actix_web::http::header::{AUTHORIZATION, USER_AGENT};
+use actix_web::{error, http, web, FromRequest, HttpRequest, HttpResponse};
 use bigdecimal::BigDecimal;
+use chrono::Duration;
+use diesel::dsl::now;
+use diesel::expression::BoxableExpression;
 use diesel::pg::expression::dsl::any;
-use diesel::OptionalExtension;
+use diesel::pg::Pg;
+use diesel::pg::PgConnection;
+use diesel::sql_types::{Bool, Text};
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, PgTextExpressionMethods, QueryDsl,
-    RunQueryDsl, TextExpressionMethods,
+    BelongingToDsl, BoolExpressionMethods, Connection, ExpressionMethods,
+    NullableExpressionMethods, PgTextExpressionMethods, QueryDsl, RunQueryDsl,
 };
+use diesel::{IntoSql, OptionalExtension, SelectableExpression};
 use std::io::Read;
-use std::str::FromStr;
-use std::{env, thread};
-use tokio::runtime::Runtime;
 
+// public sign-up, admins are never created through the API.
 #[derive(Deserialize)]
 pub struct UserForm {
     email: String,
-    admin: bool,
     password: String,
 }
 
+impl UserForm {
+    fn validate(self) -> Result<UserForm, ApiError> {
+        let mut validator = Validator::new();
+        let form = UserForm {
+            email: validator.email("email", &self.email),
+            password: self.password,
+        };
+        validator.password("password", &form.password);
+        validator.finish()?;
+        Ok(form)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordForm {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
+    token: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordForm {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct UserRoleForm {
+    admin: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ApiTokenForm {
+    name: String,
+    scopes: Vec<ApiScopeEnum>,
+}
+
+const MAX_API_TOKEN_NAME_LENGTH: usize = 100;
+
+impl ApiTokenForm {
+    fn validate(self) -> Result<ApiTokenForm, ApiError> {
+        let mut validator = Validator::new();
+        let form = ApiTokenForm {
+            name: validator.text("name", &self.name, MAX_API_TOKEN_NAME_LENGTH),
+            scopes: self.scopes,
+        };
+        if form.name.is_empty() {
+            validator.add(Some("name"), "name is required");
+        }
+        if form.scopes.is_empty() {
+            validator.add(Some("scopes"), "scopes must not be empty");
+        }
+        validator.finish()?;
+        Ok(form)
+    }
+}
+
 #[derive(Deserialize)]
 pub struct LoginForm {
     email: String,
     password: String,
 }
 
+// what clients send to create or replace a recommendation, never its id or owner.
 #[derive(Deserialize, AsChangeset)]
 #[table_name = "recos"]
 pub struct WineRecommendationForm {
+    #[serde(default)]
     pub country: String,
+    #[serde(default)]
     pub region: String,
+    #[serde(default)]
     pub designation_of_origin: String,
+    #[serde(default)]
     pub producer: String,
     pub rating: i32,
     pub color: WineColorEnum,
+    #[serde(default)]
     pub grape_variety: String,
+    #[serde(default)]
+    pub wine_name: String,
+}
+
+const MAX_CRITERION_LENGTH: usize = 100;
+
+impl WineRecommendationForm {
+    // returns the form with trimmed criteria, or every invalid field.
+    fn validate(self, rating_scale: RatingScale) -> Result<WineRecommendationForm, ApiError> {
+        let mut validator = Validator::new();
+        let form = WineRecommendationForm {
+            country: validator.text("country", &self.country, MAX_CRITERION_LENGTH),
+            region: validator.text("region", &self.region, MAX_CRITERION_LENGTH),
+            designation_of_origin: validator.text(
+                "designation_of_origin",
+                &self.designation_of_origin,
+                MAX_CRITERION_LENGTH,
+            ),
+            producer: validator.text("producer", &self.producer, MAX_CRITERION_LENGTH),
+            rating: self.rating,
+            color: self.color,
+            grape_variety: validator.text(
+                "grape_variety",
+                &self.grape_variety,
+                MAX_CRITERION_LENGTH,
+            ),
+            wine_name: validator.text("wine_name", &self.wine_name, MAX_CRITERION_LENGTH),
+        };
+        validator.rating("rating", form.rating, rating_scale);
+        // a color alone would match a third of the catalogue
+        let criteria = [
+            &form.country,
+            &form.region,
+            &form.designation_of_origin,
+            &form.producer,
+            &form.grape_variety,
+            &form.wine_name,
+        ];
+        if criteria.iter().all(|criterion| criterion.is_empty()) {
+            validator.add(
+                None,
+                "one of country, region, designation_of_origin, producer, grape_variety or wine_name is required",
+            );
+        }
+        validator.finish()?;
+        Ok(form)
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct WineCriteria {
     min_rating: Option<i32>,
-    max_price: Option<String>,
+    max_price: Option<BigDecimal>,
     color: Option<WineColorEnum>,
     available_online: Option<bool>,
+    country: Option<String>,
+    region: Option<String>,
+    designation_of_origin: Option<String>,
+    producer: Option<String>,
+    // comma separated, see grape_match
+    grape_varieties: Option<String>,
+    grape_match: Option<GrapeMatch>,
+    min_alcohol: Option<BigDecimal>,
+    max_alcohol: Option<BigDecimal>,
+    // in ml
+    volume: Option<BigDecimal>,
+    min_price: Option<BigDecimal>,
+    regulated_only: Option<bool>,
+    name: Option<String>,
+    // GET /catalogue/ only, hides wines matching none of the caller's recommendations
+    matched_only: Option<bool>,
+    // GET /search/ only, free text matched against name, producer, region and grapes
+    q: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<WineSort>,
+    order: Option<SortOrder>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WineSort {
+    PricePerLitre,
+    Price,
+    Rating,
+    Name,
+    Alcohol,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrapeMatch {
+    Any,
+    All,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+// ids that don't parse can't match anything.
+fn path_id(req: &HttpRequest, name: &str, resource: &'static str) -> Result<i32, ApiError> {
+    req.match_info()
+        .get(name)
+        .and_then(|id| id.parse::<i32>().ok())
+        .ok_or(ApiError::NotFound(resource))
+}
+
+// the session of the logged in user, None for anonymous requests.
+async fn current_session(
+    pool: &DbPool,
+    identity: &Identity,
+) -> Result<Option<(Session, User)>, ApiError> {
+    let session_token = match identity.identity() {
+        Some(session_token) => session_token,
+        None => return Ok(None),
+    };
+    let session = db::run(pool, move |conn| find_session(conn, &session_token)).await?;
+    // revoked or expired, the caller is anonymous again
+    if session.is_none() {
+        identity.forget();
+    }
+    Ok(session)
+}
+
+async fn current_user(pool: &DbPool, identity: &Identity) -> Result<Option<User>, ApiError> {
+    Ok(current_session(pool, identity).await?.map(|(_, user)| user))
+}
+
+async fn logged_in_user(pool: &DbPool, identity: &Identity) -> Result<User, ApiError> {
+    current_user(pool, identity)
+        .await?
+        .ok_or(ApiError::Unauthorized)
+}
+
+async fn logged_in_admin(pool: &DbPool, identity: &Identity) -> Result<User, ApiError> {
+    let user = logged_in_user(pool, identity).await?;
+    if !user.admin {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(user)
+}
+
+// guards admin endpoints, machine clients send Authorization: Bearer <api token>
+// and are limited to the scopes of their token.
+async fn admin_user(
+    req: &HttpRequest,
+    pool: &DbPool,
+    identity: &Identity,
+    scope: ApiScopeEnum,
+) -> Result<User, ApiError> {
+    let authorization = match req.headers().get(AUTHORIZATION) {
+        Some(authorization) => authorization,
+        None => return logged_in_admin(pool, identity).await,
+    };
+    let token = authorization
+        .to_str()
+        .ok()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .ok_or(ApiError::Unauthorized)?
+        .to_string();
+    let (api_token, user) = db::run(pool, move |conn| find_api_token(conn, &token))
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    // tokens die with the admin role of whoever created them
+    if !user.admin || !api_token.scopes.contains(&scope) {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(user)
 }
 
 pub async fn crawl_saq_controller(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    let headers = req.headers();
-    let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
-    if !headers.contains_key(AUTHORIZATION)
-        || headers.get(AUTHORIZATION).unwrap().to_str().unwrap() != secret_key
-    {
-        return Err(error::ErrorForbidden(""));
-    }
-    thread::spawn(move || {
-        let rt = Runtime::new();
-        let origin_url = String::from("https://www.saq.com/en/products/wine");
-        rt.unwrap().block_on(crawl_saq(&origin_url));
-    });
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    admin_user(&req, &pool, &identity, ApiScopeEnum::Crawl).await?;
+    let crawl_run = db::run(&pool, |conn| start_crawl(conn)).await?;
+    match crawl_run {
+        Some(crawl_run) => Ok(HttpResponse::Ok().json(crawl_run)),
+        None => Err(ApiError::Conflict {
+            field: None,
+            message: "a crawl is already running".to_string(),
+        }
+        .into()),
+    }
+}
+
+pub async fn cancel_crawl_controller(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    admin_user(&req, &pool, &identity, ApiScopeEnum::Crawl).await?;
+    let crawl_run = db::run(&pool, |conn| match get_running_crawl_run(conn)? {
+        // the crawler stops once it is done with the page it is on.
+        Some(crawl_run) => request_crawl_run_cancel(conn, crawl_run.id).map(Some),
+        None => Ok(None),
+    })
+    .await?;
+    match crawl_run {
+        Some(crawl_run) => Ok(HttpResponse::Ok().json(crawl_run)),
+        None => Err(ApiError::NotFound("running crawl").into()),
+    }
+}
+
+pub async fn get_crawl_runs(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    admin_user(&req, &pool, &identity, ApiScopeEnum::Crawl).await?;
+    let runs = db::run(&pool, |conn| {
+        crawl_runs::table
+            .order(crawl_runs::started_at.desc())
+            .limit(50)
+            .load::<CrawlRun>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "results": runs })))
+}
 
-    Ok(HttpResponse::Ok().body("Crawl has been started"))
+pub async fn get_crawl_run(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    admin_user(&req, &pool, &identity, ApiScopeEnum::Crawl).await?;
+    let crawl_run_id = path_id(&req, "crawl_run_id", "crawl run")?;
+    let crawl_run = db::run(&pool, move |conn| {
+        let crawl_run = crawl_runs::table
+            .find(crawl_run_id)
+            .first::<CrawlRun>(conn)
+            .optional()?;
+        match crawl_run {
+            Some(crawl_run) => {
+                let failures = CrawlFailure::belonging_to(&crawl_run)
+                    .order(crawl_failures::created_at)
+                    .load::<CrawlFailure>(conn)?;
+                Ok(Some((crawl_run, failures)))
+            }
+            None => Ok(None),
+        }
+    })
+    .await?;
+    let (crawl_run, failures) = crawl_run.ok_or(ApiError::NotFound("crawl run"))?;
+    Ok(HttpResponse::Ok().json(json!({ "crawl_run": crawl_run, "failures": failures })))
 }
 
 pub async fn index(_req: HttpRequest) -> Result<HttpResponse, error::Error> {
@@ -83,236 +391,778 @@ pub async fn index(_req: HttpRequest) -> Result<HttpResponse, error::Error> {
     Ok(HttpResponse::Ok().set(ContentType::html()).body(buffer))
 }
 
+// where the password reset email links to, the form posts to reset_password.
+pub async fn reset_password_page(_req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let mut buffer = Vec::new();
+    NamedFile::open("./static/html/reset_password.html")?
+        .file()
+        .read_to_end(&mut buffer)?;
+    Ok(HttpResponse::Ok().set(ContentType::html()).body(buffer))
+}
+
+// creates a new verification token, the email is sent once the connection is released.
+fn verification_email(conn: &PgConnection, user: &User) -> diesel::QueryResult<Email> {
+    let token = create_user_token(
+        conn,
+        user.id,
+        TokenPurposeEnum::EmailVerification,
+        Duration::hours(env_or("EMAIL_VERIFICATION_HOURS", 48)),
+    )?;
+    Ok(Email {
+        to: user.email.clone(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Follow this link to verify your email address:\n{}/users/verify/?token={}",
+            base_url(),
+            token
+        ),
+    })
+}
+
+// creates a reset token when the email belongs to someone.
+fn password_reset_email(conn: &PgConnection, address: &str) -> diesel::QueryResult<Option<Email>> {
+    let user = users::table
+        .filter(users::email.eq(normalize_email(address)))
+        .first::<User>(conn)
+        .optional()?;
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let token = create_user_token(
+        conn,
+        user.id,
+        TokenPurposeEnum::PasswordReset,
+        Duration::minutes(env_or("PASSWORD_RESET_MINUTES", 60)),
+    )?;
+    Ok(Some(Email {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Follow this link to choose a new password:\n{}/password/reset/?token={}\n\nIgnore this email if you didn't ask to reset your password.",
+            base_url(),
+            token
+        ),
+    }))
+}
+
 pub async fn register(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    let headers = req.headers();
-    let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
-    if !headers.contains_key(AUTHORIZATION)
-        || headers.get(AUTHORIZATION).unwrap().to_str().unwrap() != secret_key
-    {
-        return Err(error::ErrorForbidden(""));
-    }
-    let user_form_result = web::Json::<UserForm>::extract(&req).await;
-    if user_form_result.is_err() {
-        return Ok(HttpResponse::new(http::StatusCode::BAD_REQUEST));
-    }
-    let user_form = user_form_result.unwrap();
-    let connection = establish_connection();
-    let salt = compute_salt(&user_form.email);
-    let password = hash_password(&user_form.password, salt.clone());
-    let user = create_user(
-        &connection,
-        &user_form.email,
-        &user_form.admin,
-        &salt,
-        &password,
-    );
-    Ok(HttpResponse::Ok().body(format!(
-        "User with email {} has been created successfully!",
-        user.email
-    )))
+    let user_form = web::Json::<UserForm>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner()
+        .validate()?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let (user, email) = db::run(&pool, move |conn| {
+        let password_hash = hash_password(&user_form.password);
+        // an account without a token could never be verified
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let user = create_user(conn, &user_form.email, &false, &password_hash)?;
+            let email = verification_email(conn, &user)?;
+            Ok((user, email))
+        })
+    })
+    .await?;
+    // the user can already log in, the mail failing doesn't undo the sign-up
+    send_in_background(email);
+    Ok(HttpResponse::Created().json(json!({
+        "id": user.id,
+        "email": user.email,
+        "email_verified": false,
+    })))
+}
+
+// the link sent by verification_email, tokens work once.
+pub async fn verify_email(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let token_query = web::Query::<TokenQuery>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = db::run(&pool, move |conn| {
+        match use_user_token(
+            conn,
+            &token_query.token,
+            TokenPurposeEnum::EmailVerification,
+        )? {
+            Some(user_token) => verify_user_email(conn, user_token.user_id).map(Some),
+            None => Ok(None),
+        }
+    })
+    .await?;
+    let user = user.ok_or(ApiError::invalid(
+        "token",
+        "token is invalid, used or expired",
+    ))?;
+    Ok(HttpResponse::Ok().json(json!({
+        "id": user.id,
+        "email": user.email,
+        "email_verified": true,
+    })))
+}
+
+// sends a new link, the previous one stops working.
+pub async fn resend_verification_email(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    if user.email_verified_at.is_some() {
+        return Err(ApiError::Conflict {
+            field: Some("email".to_string()),
+            message: "email is already verified".to_string(),
+        }
+        .into());
+    }
+    let email = db::run(&pool, move |conn| verification_email(conn, &user)).await?;
+    send_in_background(email);
+    Ok(HttpResponse::Accepted().finish())
 }
 
 pub async fn login(req: HttpRequest) -> Result<HttpResponse, error::Error> {
     use crate::schema::users::dsl::*;
-    let login_form_result = web::Json::<LoginForm>::extract(&req).await;
-    if login_form_result.is_err() {
-        return Ok(HttpResponse::new(http::StatusCode::BAD_REQUEST));
-    }
-    let login_form = login_form_result.unwrap();
-    let conn = establish_connection();
-    let user_result = users
-        .filter(email.eq(login_form.email.clone()))
-        .first::<User>(&conn);
-    if user_result.is_err() {
-        return Err(error::ErrorBadRequest(""));
-    }
-    let user = user_result.unwrap();
-    if hash_password(&login_form.password, user.salt) == user.password {
-        let identity = Identity::extract(&req).await?;
-        // congrats you're in :)
-        identity.remember(user.email);
-        return Ok(HttpResponse::Ok().finish());
+    let login_form = web::Json::<LoginForm>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let user_agent: Option<String> = req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .map(|user_agent| user_agent.chars().take(255).collect());
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    // hashing is as blocking as the query
+    let session_token = db::run(&pool, move |conn| {
+        let user = users
+            .filter(email.eq(normalize_email(&login_form.email)))
+            .first::<User>(conn)
+            .optional()?;
+        let user = match user {
+            Some(user) => user,
+            None => {
+                dummy_password_check(&login_form.password);
+                return Ok(None);
+            }
+        };
+        match verify_password(&user, &login_form.password) {
+            PasswordCheck::Invalid => return Ok(None),
+            // the parameters changed or the user still has a legacy hash
+            PasswordCheck::ValidNeedsRehash => {
+                update_password_hash(conn, user.id, &hash_password(&login_form.password))?;
+            }
+            PasswordCheck::Valid => {}
+        }
+        create_session(conn, user.id, user_agent.as_deref()).map(|(_, token)| Some(token))
+    })
+    .await?;
+    // unknown emails and wrong passwords look the same
+    let session_token = session_token.ok_or(LoginError::ValidationError)?;
+    let identity = Identity::extract(&req).await?;
+    // congrats you're in :)
+    identity.remember(session_token);
+    Ok(HttpResponse::Ok().finish())
+}
+
+// always accepted, whether the email belongs to a user or not.
+pub async fn forgot_password(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let forgot_password_form = web::Json::<ForgotPasswordForm>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    // answered before the lookup, known and unknown emails take the same time.
+    // db::run logs its own errors and its blocking pool bounds the threads.
+    actix_rt::spawn(async move {
+        let address = forgot_password_form.email;
+        let reset_email = db::run(&pool, move |conn| password_reset_email(conn, &address)).await;
+        if let Ok(Some(reset_email)) = reset_email {
+            send_in_background(reset_email);
+        }
+    });
+    Ok(HttpResponse::Accepted().finish())
+}
+
+// whoever had the old password is logged out too.
+pub async fn reset_password(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let reset_password_form = web::Json::<ResetPasswordForm>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let mut validator = Validator::new();
+    validator.password("password", &reset_password_form.password);
+    validator.finish()?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = db::run(&pool, move |conn| {
+        let password_hash = hash_password(&reset_password_form.password);
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let user_token = match use_user_token(
+                conn,
+                &reset_password_form.token,
+                TokenPurposeEnum::PasswordReset,
+            )? {
+                Some(user_token) => user_token,
+                None => return Ok(None),
+            };
+            let user = update_password_hash(conn, user_token.user_id, &password_hash)?;
+            revoke_user_sessions(conn, user.id)?;
+            Ok(Some(user))
+        })
+    })
+    .await?;
+    user.ok_or(ApiError::invalid(
+        "token",
+        "token is invalid, used or expired",
+    ))?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// the caller stays logged in, their other sessions are revoked.
+pub async fn change_password(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let change_password_form = web::Json::<ChangePasswordForm>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let (session, user) = current_session(&pool, &identity)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    let mut validator = Validator::new();
+    validator.password("new_password", &change_password_form.new_password);
+    validator.finish()?;
+    let changed = db::run(&pool, move |conn| {
+        if verify_password(&user, &change_password_form.current_password) == PasswordCheck::Invalid
+        {
+            return Ok(false);
+        }
+        let password_hash = hash_password(&change_password_form.new_password);
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            update_password_hash(conn, user.id, &password_hash)?;
+            revoke_other_sessions(conn, &session)?;
+            discard_user_tokens(conn, user.id, TokenPurposeEnum::PasswordReset)?;
+            Ok(true)
+        })
+    })
+    .await?;
+    if !changed {
+        return Err(ApiError::invalid("current_password", "current password is incorrect").into());
     }
-    Ok(LoginError::ValidationError.error_response())
+    Ok(HttpResponse::Ok().finish())
 }
 
-pub async fn logout(identity: Identity) -> Result<HttpResponse, error::Error> {
+pub async fn logout(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    if let Some(session_token) = identity.identity() {
+        let pool = web::Data::<DbPool>::extract(&req).await?;
+        db::run(&pool, move |conn| revoke_session(conn, &session_token)).await?;
+    }
     identity.forget();
     Ok(HttpResponse::Ok().finish())
 }
 
-pub async fn create_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    use crate::schema::users::dsl::*;
+#[derive(Serialize)]
+pub struct SessionDetail {
+    #[serde(flatten)]
+    session: Session,
+    // whether this is the session making the request
+    current: bool,
+}
 
-    let wine_reco_result = web::Json::<NewWineRecommendation>::extract(&req).await;
+// where the caller is logged in, most recently used first.
+pub async fn get_sessions(req: HttpRequest) -> Result<HttpResponse, error::Error> {
     let identity = Identity::extract(&req).await?;
-    let conn = establish_connection();
-    if identity.identity().is_some() {
-        let user = users
-            .filter(email.eq(identity.identity().unwrap()))
-            .first::<User>(&conn)
-            .unwrap();
-        if wine_reco_result.is_err() {
-            return Err(error::ErrorBadRequest("malformed new wine recommendation"));
-        }
-        let mut new_wine_recommendation = wine_reco_result.unwrap();
-        new_wine_recommendation.user_id = Some(user.id);
-        create_wine_recommendation(&conn, &new_wine_recommendation);
-        return Ok(HttpResponse::new(http::StatusCode::CREATED));
-    } else {
-        return Err(error::ErrorUnauthorized(""));
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let (caller_session, user) = current_session(&pool, &identity)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    let sessions = db::run(&pool, move |conn| {
+        Session::belonging_to(&user)
+            .filter(sessions::expires_at.gt(now))
+            .order(sessions::last_seen_at.desc())
+            .load::<Session>(conn)
+    })
+    .await?;
+    let results: Vec<SessionDetail> = sessions
+        .into_iter()
+        .map(|session| SessionDetail {
+            current: session.id == caller_session.id,
+            session: session,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+}
+
+// logs out the device of the session, which may be the caller's.
+pub async fn delete_session(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let session_id = path_id(&req, "session_id", "session")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let (caller_session, user) = current_session(&pool, &identity)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    let deleted = db::run(&pool, move |conn| {
+        diesel::delete(
+            sessions::table
+                .filter(sessions::user_id.eq(user.id))
+                .filter(sessions::id.eq(session_id)),
+        )
+        .execute(conn)
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound("session").into());
+    }
+    if session_id == caller_session.id {
+        identity.forget();
+    }
+    Ok(HttpResponse::new(http::StatusCode::OK))
+}
+
+pub async fn get_users(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    admin_user(&req, &pool, &identity, ApiScopeEnum::Users).await?;
+    let results = db::run(&pool, |conn| {
+        users::table.order(users::id).load::<User>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+}
+
+// grants or removes the admin role, removing it also revokes the user's API tokens.
+pub async fn update_user_role(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let user_role_result = web::Json::<UserRoleForm>::extract(&req).await;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let admin = admin_user(&req, &pool, &identity, ApiScopeEnum::Users).await?;
+    let user_id = path_id(&req, "user_id", "user")?;
+    let user_role_form = user_role_result
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    // there would be no one left to give it back
+    if user_id == admin.id && !user_role_form.admin {
+        return Err(ApiError::invalid("admin", "admins can't remove their own admin role").into());
+    }
+    let user = db::run(&pool, move |conn| {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let user = diesel::update(users::table.find(user_id))
+                .set(users::admin.eq(user_role_form.admin))
+                .get_result::<User>(conn)
+                .optional()?;
+            if !user_role_form.admin {
+                diesel::delete(api_tokens::table.filter(api_tokens::user_id.eq(user_id)))
+                    .execute(conn)?;
+            }
+            Ok(user)
+        })
+    })
+    .await?;
+    let user = user.ok_or(ApiError::NotFound("user"))?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+// everything the user owns goes with them.
+pub async fn delete_user(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let admin = admin_user(&req, &pool, &identity, ApiScopeEnum::Users).await?;
+    let user_id = path_id(&req, "user_id", "user")?;
+    if user_id == admin.id {
+        return Err(ApiError::Forbidden.into());
+    }
+    let deleted = db::run(&pool, move |conn| {
+        diesel::delete(users::table.find(user_id)).execute(conn)
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound("user").into());
+    }
+    Ok(HttpResponse::new(http::StatusCode::OK))
+}
+
+// only from an admin session, API tokens can't create more of themselves.
+pub async fn create_api_token_controller(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let api_token_result = web::Json::<ApiTokenForm>::extract(&req).await;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let admin = logged_in_admin(&pool, &identity).await?;
+    let api_token_form = api_token_result
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner()
+        .validate()?;
+    let (api_token, token) = db::run(&pool, move |conn| {
+        create_api_token(conn, admin.id, &api_token_form.name, &api_token_form.scopes)
+    })
+    .await?;
+    // the token can't be shown again, only its hash is stored
+    Ok(HttpResponse::Created().json(json!({ "api_token": api_token, "token": token })))
+}
+
+pub async fn get_api_tokens(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let admin = logged_in_admin(&pool, &identity).await?;
+    let results = db::run(&pool, move |conn| {
+        ApiToken::belonging_to(&admin)
+            .order(api_tokens::created_at.desc())
+            .load::<ApiToken>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+}
+
+pub async fn delete_api_token(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let admin = logged_in_admin(&pool, &identity).await?;
+    let api_token_id = path_id(&req, "api_token_id", "api token")?;
+    let deleted = db::run(&pool, move |conn| {
+        diesel::delete(
+            api_tokens::table
+                .filter(api_tokens::user_id.eq(admin.id))
+                .filter(api_tokens::id.eq(api_token_id)),
+        )
+        .execute(conn)
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound("api token").into());
+    }
+    Ok(HttpResponse::new(http::StatusCode::OK))
+}
+
+pub async fn create_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let wine_reco_result = web::Json::<WineRecommendationForm>::extract(&req).await;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    if user.email_verified_at.is_none() {
+        return Err(ApiError::EmailNotVerified.into());
     }
+    let wine_recommendation_form = wine_reco_result
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner()
+        .validate(RatingScale::from_env())?;
+    let new_wine_recommendation = NewWineRecommendation {
+        country: wine_recommendation_form.country,
+        region: wine_recommendation_form.region,
+        designation_of_origin: wine_recommendation_form.designation_of_origin,
+        producer: wine_recommendation_form.producer,
+        rating: wine_recommendation_form.rating,
+        color: wine_recommendation_form.color,
+        grape_variety: wine_recommendation_form.grape_variety,
+        user_id: Some(user.id),
+        wine_name: wine_recommendation_form.wine_name,
+    };
+    let wine_recommendation = db::run(&pool, move |conn| {
+        let wine_recommendation = create_wine_recommendation(conn, &new_wine_recommendation)?;
+        refresh_wine_recommendation_matches(conn, wine_recommendation.id)?;
+        Ok(wine_recommendation)
+    })
+    .await?;
+    Ok(HttpResponse::Created()
+        .header(
+            http::header::LOCATION,
+            wine_recommendation_location(wine_recommendation.id),
+        )
+        .json(wine_recommendation))
+}
+
+fn wine_recommendation_location(wine_recommendation_id: i32) -> String {
+    format!("/winerecommendations/{}/", wine_recommendation_id)
 }
 
 pub async fn get_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    use crate::schema::users::dsl::*;
     let identity = Identity::extract(&req).await?;
-    let conn = establish_connection();
-    if identity.identity().is_some() {
-        let user = users
-            .filter(email.eq(identity.identity().unwrap()))
-            .first::<User>(&conn)
-            .unwrap();
-        let query = recos::table
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let query = db::run(&pool, move |conn| {
+        recos::table
             .filter(recos::user_id.eq(user.id))
-            .load::<WineRecommendation>(&conn)
-            .expect("Error fetching wine recommendations.");
-        return Ok(HttpResponse::Ok().json(query));
-    } else {
-        return Err(error::ErrorUnauthorized(""));
-    }
+            .load::<WineRecommendation>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(query))
 }
 
 pub async fn update_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    use crate::schema::users::dsl::*;
     use crate::schema::wine_recommendations;
     let wine_reco_result = web::Json::<WineRecommendationForm>::extract(&req).await;
     let identity = Identity::extract(&req).await?;
-    let conn = establish_connection();
-    let parsed_wine_reco_id = req
-        .match_info()
-        .get("wine_recommendation_id")
-        .unwrap()
-        .parse::<i32>();
-    if parsed_wine_reco_id.is_err() {
-        return Err(error::ErrorNotFound(""));
-    }
-    let wine_recommendation_id = parsed_wine_reco_id.unwrap();
-    if identity.identity().is_some() {
-        let user = users
-            .filter(email.eq(identity.identity().unwrap()))
-            .first::<User>(&conn)
-            .unwrap();
-        if wine_reco_result.is_err() {
-            return Err(error::ErrorBadRequest(""));
-        }
+    let wine_recommendation_id = path_id(&req, "wine_recommendation_id", "wine recommendation")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let wine_recommendation_form: WineRecommendationForm = wine_reco_result
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner()
+        .validate(RatingScale::from_env())?;
+    let wine_recommendation = db::run(&pool, move |conn| {
         let target = wine_recommendations::table.filter(
             wine_recommendations::dsl::user_id
                 .eq(user.id)
                 .and(wine_recommendations::dsl::id.eq(wine_recommendation_id)),
         );
-        let wine_recommendation_form: WineRecommendationForm =
-            wine_reco_result.unwrap().into_inner();
-        let update_result = diesel::update(target)
+        let wine_recommendation = diesel::update(target)
             .set(&wine_recommendation_form)
-            .get_result::<WineRecommendation>(&conn)
-            .optional();
-        if update_result.is_err() {
-            return Err(error::ErrorInternalServerError(
-                "Error while updating database record",
-            ));
+            .get_result::<WineRecommendation>(conn)
+            .optional()?;
+        if let Some(wine_recommendation) = &wine_recommendation {
+            refresh_wine_recommendation_matches(conn, wine_recommendation.id)?;
         }
-        return Ok(HttpResponse::new(http::StatusCode::OK));
-    } else {
-        return Err(error::ErrorUnauthorized(""));
+        Ok(wine_recommendation)
+    })
+    .await?;
+    // someone else's recommendation doesn't exist as far as the caller knows
+    let wine_recommendation =
+        wine_recommendation.ok_or(ApiError::NotFound("wine recommendation"))?;
+    Ok(HttpResponse::Ok()
+        .header(
+            http::header::LOCATION,
+            wine_recommendation_location(wine_recommendation.id),
+        )
+        .json(wine_recommendation))
+}
+
+pub async fn get_one_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let wine_recommendation_id = path_id(&req, "wine_recommendation_id", "wine recommendation")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let wine_recommendation = db::run(&pool, move |conn| {
+        recos::table
+            .filter(
+                recos::user_id
+                    .eq(user.id)
+                    .and(recos::id.eq(wine_recommendation_id)),
+            )
+            .first::<WineRecommendation>(conn)
+            .optional()
+    })
+    .await?;
+    match wine_recommendation {
+        Some(wine_recommendation) => Ok(HttpResponse::Ok().json(wine_recommendation)),
+        None => Err(ApiError::NotFound("wine recommendation").into()),
     }
 }
 
 pub async fn delete_wine_reco(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    use crate::schema::users::dsl::*;
     use crate::schema::wine_recommendations;
 
     let identity = Identity::extract(&req).await?;
-    let conn = establish_connection();
-    let parsed_wine_reco_id = req
-        .match_info()
-        .get("wine_recommendation_id")
-        .unwrap()
-        .parse::<i32>();
-    if parsed_wine_reco_id.is_err() {
-        return Err(error::ErrorNotFound("wine recommendation not found"));
-    }
-    if identity.identity().is_some() {
-        let user = users
-            .filter(email.eq(identity.identity().unwrap()))
-            .first::<User>(&conn)
-            .unwrap();
+    let wine_recommendation_id = path_id(&req, "wine_recommendation_id", "wine recommendation")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let deleted = db::run(&pool, move |conn| {
         let target = wine_recommendations::table.filter(
             wine_recommendations::dsl::user_id
                 .eq(user.id)
-                .and(wine_recommendations::dsl::id.eq(parsed_wine_reco_id.unwrap())),
+                .and(wine_recommendations::dsl::id.eq(wine_recommendation_id)),
         );
-        diesel::delete(target)
-            .execute(&conn)
-            .expect("Error deleting wine recommendation");
-        return Ok(HttpResponse::new(http::StatusCode::OK));
-    } else {
-        return Err(error::ErrorUnauthorized(""));
+        diesel::delete(target).execute(conn)
+    })
+    .await?;
+    // someone else's recommendation doesn't exist as far as the caller knows
+    if deleted == 0 {
+        return Err(ApiError::NotFound("wine recommendation").into());
     }
+    Ok(HttpResponse::new(http::StatusCode::OK))
 }
 
-pub async fn get_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    let wine_criteria_result = web::Query::<WineCriteria>::extract(&req).await;
-    if wine_criteria_result.is_err() {
-        return Err(error::ErrorBadRequest(""));
+// conditions on the SAQ wine only, usable on any query selecting from saq_wines.
+fn saq_wine_filter<QS>(
+    wine_criteria: &WineCriteria,
+) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>>
+where
+    QS: 'static,
+    saq::delisted: SelectableExpression<QS>,
+    saq::color: SelectableExpression<QS>,
+    saq::available_online: SelectableExpression<QS>,
+    saq::regulated_designation: SelectableExpression<QS>,
+    saq::country: SelectableExpression<QS>,
+    saq::region: SelectableExpression<QS>,
+    saq::designation_of_origin: SelectableExpression<QS>,
+    saq::producer: SelectableExpression<QS>,
+    saq::name: SelectableExpression<QS>,
+    saq::grape_varieties: SelectableExpression<QS>,
+    saq::alcohol_percent: SelectableExpression<QS>,
+    saq::volume: SelectableExpression<QS>,
+    saq::price: SelectableExpression<QS>,
+{
+    let mut filter: Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>> =
+        Box::new(saq::delisted.eq(false));
+    if let Some(color) = wine_criteria.color.clone() {
+        filter = Box::new(filter.and(saq::color.eq(color)));
     }
-    let wine_criteria = wine_criteria_result.unwrap();
-    let identity = Identity::extract(&req).await?;
-    let mut user = None;
-    let conn = establish_connection();
-    if identity.identity().is_some() {
-        user = Some(
-            users::table
-                .filter(users::email.eq(identity.identity().unwrap()))
-                .first::<User>(&conn)
-                .unwrap(),
-        );
+    if let Some(available_online) = wine_criteria.available_online {
+        filter = Box::new(filter.and(saq::available_online.eq(available_online)));
     }
-    let mut wines_query = saq::table
-        .inner_join(
-            recos::table.on(recos::country
-                .eq("")
-                .or(saq::country.ilike(recos::country))
-                .and(
-                    recos::region
-                        .eq("")
-                        .or(saq::region.ilike(recos::region))
-                        .and(
-                            recos::designation_of_origin
-                                .eq("")
-                                .or(saq::designation_of_origin
-                                    .ilike(recos::designation_of_origin.concat("%")))
-                                .and(
-                                    recos::wine_name
-                                        .eq("")
-                                        .or(saq::name.ilike(recos::wine_name.concat("%")))
-                                        .and(
-                                            recos::producer
-                                                .eq("")
-                                                .or(saq::producer.ilike(recos::producer))
-                                                .and(
-                                                    recos::grape_variety
-                                                        .eq("")
-                                                        .or(recos::grape_variety
-                                                            .ilike(any(saq::grape_varieties)))
-                                                        .and(saq::color.eq(recos::color)),
-                                                ),
-                                        ),
-                                ),
-                        ),
-                )),
-        )
-        .select((
+    if wine_criteria.regulated_only == Some(true) {
+        filter = Box::new(filter.and(saq::regulated_designation.eq(true)));
+    }
+    if let Some(country) = &wine_criteria.country {
+        filter = Box::new(filter.and(saq::country.ilike(escape_like(country))));
+    }
+    if let Some(region) = &wine_criteria.region {
+        filter = Box::new(filter.and(saq::region.ilike(escape_like(region))));
+    }
+    if let Some(designation_of_origin) = &wine_criteria.designation_of_origin {
+        filter = Box::new(filter.and(
+            saq::designation_of_origin.ilike(format!("{}%", escape_like(designation_of_origin))),
+        ));
+    }
+    if let Some(producer) = &wine_criteria.producer {
+        filter = Box::new(filter.and(saq::producer.ilike(escape_like(producer))));
+    }
+    if let Some(name) = &wine_criteria.name {
+        filter = Box::new(filter.and(saq::name.ilike(format!("%{}%", escape_like(name.trim())))));
+    }
+    if let Some(grape_varieties) = &wine_criteria.grape_varieties {
+        let match_all = wine_criteria.grape_match == Some(GrapeMatch::All);
+        let mut grape_filter: Option<Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>>> = None;
+        for grape_variety in grape_varieties
+            .split(',')
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+        {
+            let has_grape_variety = escape_like(grape_variety)
+                .into_sql::<Text>()
+                .ilike(any(saq::grape_varieties));
+            grape_filter = Some(match grape_filter {
+                None => Box::new(has_grape_variety),
+                Some(previous) if match_all => Box::new(previous.and(has_grape_variety)),
+                Some(previous) => Box::new(previous.or(has_grape_variety)),
+            });
+        }
+        if let Some(grape_filter) = grape_filter {
+            filter = Box::new(filter.and(grape_filter));
+        }
+    }
+    if let Some(min_alcohol) = wine_criteria.min_alcohol.clone() {
+        filter = Box::new(filter.and(saq::alcohol_percent.ge(min_alcohol)));
+    }
+    if let Some(max_alcohol) = wine_criteria.max_alcohol.clone() {
+        filter = Box::new(filter.and(saq::alcohol_percent.le(max_alcohol)));
+    }
+    if let Some(volume) = wine_criteria.volume.clone() {
+        filter = Box::new(filter.and(saq::volume.eq(volume)));
+    }
+    // prices are compared for one bottle, considered to be 750 mL
+    if let Some(min_price) = &wine_criteria.min_price {
+        let min_bottle_price = min_price / BigDecimal::from(750);
+        filter = Box::new(filter.and((saq::price / saq::volume).ge(min_bottle_price)));
+    }
+    if let Some(max_price) = &wine_criteria.max_price {
+        let max_bottle_price = max_price / BigDecimal::from(750);
+        filter = Box::new(filter.and((saq::price / saq::volume).le(max_bottle_price)));
+    }
+    filter
+}
+
+fn page_criteria(wine_criteria: &WineCriteria) -> Result<(i64, i64), ApiError> {
+    let page = wine_criteria.page.unwrap_or(1);
+    let per_page = wine_criteria.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page < 1 {
+        return Err(ApiError::invalid("page", "page must be at least 1"));
+    }
+    if per_page < 1 || per_page > MAX_PER_PAGE {
+        return Err(ApiError::invalid(
+            "per_page",
+            &format!("per_page must be between 1 and {}", MAX_PER_PAGE),
+        ));
+    }
+    // the offset of the page must fit, no catalogue comes close anyway
+    if page.checked_mul(per_page).is_none() {
+        return Err(ApiError::invalid("page", "page is too large"));
+    }
+    Ok((page, per_page))
+}
+
+// rejects the criteria an endpoint would otherwise silently ignore.
+fn reject_unsupported_criteria(criteria: &[(&str, bool)]) -> Result<(), ApiError> {
+    let mut validator = Validator::new();
+    for (field, present) in criteria {
+        if *present {
+            validator.add(
+                Some(field),
+                &format!("{} is not supported by this endpoint", field),
+            );
+        }
+    }
+    validator.finish()
+}
+
+// the envelope of every paginated listing, next is None on the last page.
+fn paginated_json<T: serde::Serialize>(
+    req: &HttpRequest,
+    page: i64,
+    per_page: i64,
+    total: i64,
+    results: T,
+) -> HttpResponse {
+    let next = if page * per_page < total {
+        Some(next_page_link(
+            req.path(),
+            req.query_string(),
+            page + 1,
+            per_page,
+        ))
+    } else {
+        None
+    };
+    HttpResponse::Ok().json(json!({
+        "results": results,
+        "total": total,
+        "page": page,
+        "per_page": per_page,
+        "next": next,
+    }))
+}
+
+// returns the sort key and whether it is descending.
+fn sort_criteria(wine_criteria: &WineCriteria) -> (WineSort, bool) {
+    let sort = wine_criteria.sort.unwrap_or(WineSort::PricePerLitre);
+    // best rated wines first unless asked otherwise
+    let default_order = if sort == WineSort::Rating {
+        SortOrder::Desc
+    } else {
+        SortOrder::Asc
+    };
+    (
+        sort,
+        wine_criteria.order.unwrap_or(default_order) == SortOrder::Desc,
+    )
+}
+
+pub async fn get_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let wine_criteria = web::Query::<WineCriteria>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    reject_unsupported_criteria(&[
+        ("matched_only", wine_criteria.matched_only.is_some()),
+        ("q", wine_criteria.q.is_some()),
+    ])?;
+    let (page, per_page) = page_criteria(&wine_criteria)?;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user_id = current_user(&pool, &identity).await?.map(|user| user.id);
+    let (total, wines) = db::run(&pool, move |conn| {
+        // the count and the page need their own query
+        let wines_query = || {
+            let mut wines_query = wine_matches::table
+                .inner_join(saq::table)
+                .filter(saq_wine_filter(&wine_criteria))
+                .into_boxed();
+            // a wine matched by several recommendations is listed once, see the wine_matches view
+            wines_query = match user_id {
+                Some(user_id) => wines_query.filter(wine_matches::user_id.eq(user_id)),
+                None => wines_query.filter(wine_matches::user_id.is_null()),
+            };
+            if let Some(min_rating) = wine_criteria.min_rating {
+                wines_query = wines_query.filter(wine_matches::rating.ge(min_rating));
+            }
+            wines_query
+        };
+        let mut page_query = wines_query().select((
             saq::id,
             saq::name,
             saq::available_online,
@@ -323,61 +1173,46 @@ pub async fn get_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
             saq::color,
             saq::volume,
             saq::price,
-            recos::rating,
-        ))
-        .order(saq::price / saq::volume)
-        .into_boxed();
-    if wine_criteria.color.is_some() {
-        wines_query = wines_query.filter(saq::color.eq(wine_criteria.clone().color.unwrap()));
-    }
-    if wine_criteria.min_rating.is_some() {
-        wines_query = wines_query.filter(recos::rating.ge(wine_criteria.min_rating.unwrap()));
-    }
-    if wine_criteria.max_price.is_some() {
-        let max_price = BigDecimal::from_str(&wine_criteria.clone().max_price.unwrap());
-        if max_price.is_err() {
-            return Ok(HttpResponse::new(http::StatusCode::BAD_REQUEST));
-        }
-        // one bottle is considered to be 750 mL
-        let max_bottle_price = max_price.unwrap() / BigDecimal::from_str("750").unwrap();
-        wines_query = wines_query.filter((saq::price / saq::volume).le(max_bottle_price));
-    }
-    if wine_criteria.available_online.is_some() {
-        wines_query =
-            wines_query.filter(saq::available_online.eq(wine_criteria.available_online.unwrap()));
-    }
-    if user.is_some() {
-        wines_query = wines_query.filter(recos::user_id.eq(user.unwrap().id));
-    }
-
-    let mut wines: Vec<(
-        i32,
-        String,
-        bool,
-        String,
-        String,
-        String,
-        String,
-        WineColorEnum,
-        BigDecimal,
-        BigDecimal,
-        i32,
-    )> = wines_query.load(&conn).unwrap();
-
-    /* in some cases it's possible we have duplicates in our results
-    (same bottle returned by different recommendations)
-    we want to remove these duplicates and keep the recommendation
-     with the bigger rating */
-
-    let mut i = 0;
-    while i != wines.len() {
-        if is_dup_wine(&wines, &wines[i], i) {
-            wines.remove(i);
-        } else {
-            i += 1;
-        }
-    }
-
+            wine_matches::rating,
+            wine_matches::wine_recommendation_ids,
+        ));
+        page_query = match sort_criteria(&wine_criteria) {
+            (WineSort::PricePerLitre, false) => page_query.order((saq::price / saq::volume).asc()),
+            (WineSort::PricePerLitre, true) => page_query.order((saq::price / saq::volume).desc()),
+            (WineSort::Price, false) => page_query.order(saq::price.asc()),
+            (WineSort::Price, true) => page_query.order(saq::price.desc()),
+            (WineSort::Rating, false) => page_query.order(wine_matches::rating.asc()),
+            (WineSort::Rating, true) => page_query.order(wine_matches::rating.desc()),
+            (WineSort::Name, false) => page_query.order(saq::name.asc()),
+            (WineSort::Name, true) => page_query.order(saq::name.desc()),
+            (WineSort::Alcohol, false) => page_query.order(saq::alcohol_percent.asc()),
+            (WineSort::Alcohol, true) => page_query.order(saq::alcohol_percent.desc()),
+        };
+        // keeps pages stable between requests
+        page_query = page_query
+            .then_order_by(saq::id)
+            .offset((page - 1) * per_page)
+            .limit(per_page);
+        let wines: Vec<(
+            i32,
+            String,
+            bool,
+            String,
+            String,
+            String,
+            String,
+            WineColorEnum,
+            BigDecimal,
+            BigDecimal,
+            i32,
+            Vec<i32>,
+        )> = page_query.load(conn)?;
+        let total = wines_query().count().get_result::<i64>(conn)?;
+        Ok((total, wines))
+    })
+    .await?;
+    // the best rating comes before the ids of the caller's matching recommendations, visitors
+    // get none
     let results: Vec<(
         &i32,
         &String,
@@ -390,6 +1225,7 @@ pub async fn get_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
         String,
         String,
         &i32,
+        &Vec<i32>,
     )> = wines
         .iter()
         .map(|wine| {
@@ -405,13 +1241,487 @@ pub async fn get_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
                 format!("{} ml", &wine.8),
                 format!("{}", &wine.9),
                 &wine.10,
+                &wine.11,
             )
         })
         .collect();
 
-    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+    Ok(paginated_json(&req, page, per_page, total, results))
 }
 
-pub async fn get_health(_req: HttpRequest) -> Result<HttpResponse, error::Error> {
-    Ok(HttpResponse::Ok().finish())
+#[derive(Serialize)]
+pub struct CatalogueWine {
+    #[serde(flatten)]
+    saq_wine: SaqWine,
+    // ids of the caller's recommendations matching this wine
+    matching_recommendations: Vec<i32>,
+}
+
+// every wine of the catalogue, whether it matches a recommendation or not.
+pub async fn get_catalogue(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let wine_criteria = web::Query::<WineCriteria>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    reject_unsupported_criteria(&[
+        ("min_rating", wine_criteria.min_rating.is_some()),
+        ("q", wine_criteria.q.is_some()),
+    ])?;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user_id = current_user(&pool, &identity).await?.map(|user| user.id);
+    let matched_only = wine_criteria.matched_only == Some(true);
+    if matched_only && user_id.is_none() {
+        return Err(ApiError::Unauthorized.into());
+    }
+    let (page, per_page) = page_criteria(&wine_criteria)?;
+    let (sort, descending) = sort_criteria(&wine_criteria);
+    if sort == WineSort::Rating {
+        return Err(ApiError::invalid("sort", "catalogue wines have no rating").into());
+    }
+    let (total, saq_wines, matches) = db::run(&pool, move |conn| {
+        let catalogue_query = || -> saq::BoxedQuery<'static, Pg> {
+            let mut catalogue_query = saq::table
+                .filter(saq_wine_filter(&wine_criteria))
+                .into_boxed();
+            if matched_only {
+                let matched_saq_wine_ids = reco_matches::table
+                    .inner_join(recos::table)
+                    .filter(recos::user_id.eq(user_id.unwrap()))
+                    .select(reco_matches::saq_wine_id)
+                    .into_boxed();
+                catalogue_query = catalogue_query.filter(saq::id.eq_any(matched_saq_wine_ids));
+            }
+            catalogue_query
+        };
+        let total = catalogue_query().count().get_result::<i64>(conn)?;
+        let mut page_query = catalogue_query();
+        page_query = match (sort, descending) {
+            (WineSort::Price, false) => page_query.order(saq::price.asc()),
+            (WineSort::Price, true) => page_query.order(saq::price.desc()),
+            (WineSort::Name, false) => page_query.order(saq::name.asc()),
+            (WineSort::Name, true) => page_query.order(saq::name.desc()),
+            (WineSort::Alcohol, false) => page_query.order(saq::alcohol_percent.asc()),
+            (WineSort::Alcohol, true) => page_query.order(saq::alcohol_percent.desc()),
+            (_, false) => page_query.order((saq::price / saq::volume).asc()),
+            (_, true) => page_query.order((saq::price / saq::volume).desc()),
+        };
+        let saq_wines = page_query
+            .then_order_by(saq::id)
+            .offset((page - 1) * per_page)
+            .limit(per_page)
+            .load::<SaqWine>(conn)?;
+
+        // (saq_wine_id, wine_recommendation_id) for the wines of this page
+        let matches: Vec<(i32, i32)> = match user_id {
+            Some(user_id) => reco_matches::table
+                .inner_join(recos::table)
+                .filter(recos::user_id.eq(user_id))
+                .filter(
+                    reco_matches::saq_wine_id.eq_any(
+                        saq_wines
+                            .iter()
+                            .map(|saq_wine| saq_wine.id)
+                            .collect::<Vec<i32>>(),
+                    ),
+                )
+                .select((
+                    reco_matches::saq_wine_id,
+                    reco_matches::wine_recommendation_id,
+                ))
+                .order(reco_matches::wine_recommendation_id)
+                .load(conn)?,
+            None => Vec::new(),
+        };
+        Ok((total, saq_wines, matches))
+    })
+    .await?;
+    let results: Vec<CatalogueWine> = saq_wines
+        .into_iter()
+        .map(|saq_wine| CatalogueWine {
+            matching_recommendations: matches
+                .iter()
+                .filter(|(saq_wine_id, _)| *saq_wine_id == saq_wine.id)
+                .map(|(_, wine_recommendation_id)| *wine_recommendation_id)
+                .collect(),
+            saq_wine: saq_wine,
+        })
+        .collect();
+    Ok(paginated_json(&req, page, per_page, total, results))
+}
+
+#[derive(Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    saq_wine: SaqWine,
+    rank: f32,
+}
+
+// best matches first, typos and missing accents are tolerated.
+pub async fn search_wines(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let wine_criteria = web::Query::<WineCriteria>::extract(&req)
+        .await
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    let query = wine_criteria
+        .q
+        .clone()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if query.is_empty() {
+        return Err(ApiError::invalid("q", "q is required").into());
+    }
+    // results are ranked, and the catalogue has no ratings
+    reject_unsupported_criteria(&[
+        ("min_rating", wine_criteria.min_rating.is_some()),
+        ("matched_only", wine_criteria.matched_only.is_some()),
+        ("sort", wine_criteria.sort.is_some()),
+        ("order", wine_criteria.order.is_some()),
+    ])?;
+    let (page, per_page) = page_criteria(&wine_criteria)?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let (total, results) = db::run(&pool, move |conn| {
+        let total = saq::table
+            .filter(saq_wine_filter(&wine_criteria))
+            .filter(saq_wine_search(&query))
+            .count()
+            .get_result::<i64>(conn)?;
+        let results = saq::table
+            .select((saq::all_columns, saq_wine_search_rank(&query)))
+            .filter(saq_wine_filter(&wine_criteria))
+            .filter(saq_wine_search(&query))
+            .order(saq_wine_search_rank(&query).desc())
+            .then_order_by(saq::id)
+            .offset((page - 1) * per_page)
+            .limit(per_page)
+            .load::<(SaqWine, f32)>(conn)?;
+        Ok((total, results))
+    })
+    .await?;
+    let results: Vec<SearchResult> = results
+        .into_iter()
+        .map(|(saq_wine, rank)| SearchResult {
+            saq_wine: saq_wine,
+            rank: rank,
+        })
+        .collect();
+    Ok(paginated_json(&req, page, per_page, total, results))
+}
+
+#[derive(Serialize)]
+pub struct SaqWineDetail {
+    #[serde(flatten)]
+    saq_wine: SaqWine,
+    // the caller's recommendations matching this wine
+    recommendations: Vec<WineRecommendation>,
+}
+
+pub async fn get_wine(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let saq_wine_id = path_id(&req, "saq_wine_id", "wine")?;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user_id = current_user(&pool, &identity).await?.map(|user| user.id);
+    let saq_wine_detail = db::run(&pool, move |conn| {
+        let saq_wine = saq::table
+            .find(saq_wine_id)
+            .first::<SaqWine>(conn)
+            .optional()?;
+        let saq_wine = match saq_wine {
+            Some(saq_wine) => saq_wine,
+            None => return Ok(None),
+        };
+        let recommendations = match user_id {
+            Some(user_id) => recos::table
+                .inner_join(reco_matches::table)
+                .filter(recos::user_id.eq(user_id))
+                .filter(reco_matches::saq_wine_id.eq(saq_wine_id))
+                .select(recos::all_columns)
+                .load::<WineRecommendation>(conn)?,
+            None => Vec::new(),
+        };
+        Ok(Some(SaqWineDetail {
+            saq_wine: saq_wine,
+            recommendations: recommendations,
+        }))
+    })
+    .await?;
+    match saq_wine_detail {
+        Some(saq_wine_detail) => Ok(HttpResponse::Ok().json(saq_wine_detail)),
+        None => Err(ApiError::NotFound("wine").into()),
+    }
+}
+
+pub async fn get_wine_prices(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let saq_wine_id = path_id(&req, "saq_wine_id", "wine")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let saq_wine_prices = db::run(&pool, move |conn| {
+        let saq_wine = saq::table
+            .find(saq_wine_id)
+            .first::<SaqWine>(conn)
+            .optional()?;
+        match saq_wine {
+            Some(saq_wine) => SaqWinePrice::belonging_to(&saq_wine)
+                .order(saq_wine_prices::recorded_at)
+                .load::<SaqWinePrice>(conn)
+                .map(Some),
+            None => Ok(None),
+        }
+    })
+    .await?;
+    let prices: Vec<serde_json::Value> = saq_wine_prices
+        .ok_or(ApiError::NotFound("wine"))?
+        .iter()
+        .map(|saq_wine_price| {
+            json!({
+                "price": format!("{}", saq_wine_price.price),
+                "recorded_at": saq_wine_price.recorded_at,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(json!({ "saq_wine_id": saq_wine_id, "results": prices })))
+}
+
+pub async fn create_alert(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let wine_alert_result = web::Json::<NewWineAlert>::extract(&req).await;
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let mut new_wine_alert = wine_alert_result
+        .map_err(|error| ApiError::malformed(&error.to_string()))?
+        .into_inner();
+    new_wine_alert.user_id = user.id;
+    match (&new_wine_alert.kind, &new_wine_alert.max_price) {
+        (AlertKindEnum::PriceThreshold, None) => {
+            return Err(ApiError::invalid("max_price", "max_price is required").into());
+        }
+        (AlertKindEnum::PriceThreshold, Some(max_price)) if *max_price <= BigDecimal::from(0) => {
+            return Err(ApiError::invalid("max_price", "max_price must be positive").into());
+        }
+        (AlertKindEnum::PriceThreshold, _) => {}
+        (_, _) => new_wine_alert.max_price = None,
+    }
+    // None when the recommendation belongs to someone else
+    let wine_alert = db::run(&pool, move |conn| {
+        if let Some(wine_recommendation_id) = new_wine_alert.wine_recommendation_id {
+            let owned = recos::table
+                .filter(
+                    recos::user_id
+                        .eq(user.id)
+                        .and(recos::id.eq(wine_recommendation_id)),
+                )
+                .first::<WineRecommendation>(conn)
+                .optional()?;
+            if owned.is_none() {
+                return Ok(None);
+            }
+        }
+        create_wine_alert(conn, &new_wine_alert).map(Some)
+    })
+    .await?;
+    match wine_alert {
+        Some(wine_alert) => Ok(HttpResponse::Created().json(wine_alert)),
+        None => {
+            Err(ApiError::invalid("wine_recommendation_id", "wine recommendation not found").into())
+        }
+    }
+}
+
+pub async fn get_alerts(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let alerts = db::run(&pool, move |conn| {
+        WineAlert::belonging_to(&user)
+            .order(wine_alerts::id)
+            .load::<WineAlert>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "results": alerts })))
+}
+
+pub async fn delete_alert(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let wine_alert_id = path_id(&req, "wine_alert_id", "wine alert")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let deleted = db::run(&pool, move |conn| {
+        let target = wine_alerts::table.filter(
+            wine_alerts::user_id
+                .eq(user.id)
+                .and(wine_alerts::id.eq(wine_alert_id)),
+        );
+        diesel::delete(target).execute(conn)
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(ApiError::NotFound("wine alert").into());
+    }
+    Ok(HttpResponse::new(http::StatusCode::OK))
+}
+
+// the in-app inbox, unread notifications first.
+pub async fn get_notifications(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let notifications = db::run(&pool, move |conn| {
+        Notification::belonging_to(&user)
+            .order((
+                notifications::read_at.is_not_null(),
+                notifications::created_at.desc(),
+            ))
+            .limit(100)
+            .load::<Notification>(conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({ "results": notifications })))
+}
+
+pub async fn read_notification(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let identity = Identity::extract(&req).await?;
+    let notification_id = path_id(&req, "notification_id", "notification")?;
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let user = logged_in_user(&pool, &identity).await?;
+    let notification = db::run(&pool, move |conn| {
+        let target = notifications::table.filter(
+            notifications::user_id
+                .eq(user.id)
+                .and(notifications::id.eq(notification_id)),
+        );
+        diesel::update(target)
+            .set(notifications::read_at.eq(now.nullable()))
+            .get_result::<Notification>(conn)
+            .optional()
+    })
+    .await?;
+    match notification {
+        Some(notification) => Ok(HttpResponse::Ok().json(notification)),
+        None => Err(ApiError::NotFound("notification").into()),
+    }
+}
+
+// 503 when the database can't be reached, so load balancers stop routing here.
+pub async fn get_health(req: HttpRequest) -> Result<HttpResponse, error::Error> {
+    let pool = web::Data::<DbPool>::extract(&req).await?;
+    let pool_state = pool.state();
+    let database_up = db::run(&pool, |conn| diesel::sql_query("SELECT 1").execute(conn))
+        .await
+        .is_ok();
+    let health = json!({
+        "database": if database_up { "up" } else { "down" },
+        "pool": {
+            "connections": pool_state.connections,
+            "idle_connections": pool_state.idle_connections,
+            "max_size": pool.max_size(),
+        },
+    });
+    if database_up {
+        Ok(HttpResponse::Ok().json(health))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(health))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wine_recommendation_form() -> WineRecommendationForm {
+        WineRecommendationForm {
+            country: " France ".to_string(),
+            region: "".to_string(),
+            designation_of_origin: "".to_string(),
+            producer: "".to_string(),
+            rating: 90,
+            color: WineColorEnum::Red,
+            grape_variety: "".to_string(),
+            wine_name: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_wine_recommendation_form() {
+        let form = wine_recommendation_form()
+            .validate(RatingScale::Hundred)
+            .unwrap();
+        assert_eq!(form.country, "France");
+
+        let mut form = wine_recommendation_form();
+        form.country = "   ".to_string();
+        form.rating = 6;
+        match form.validate(RatingScale::Five) {
+            Err(ApiError::InvalidFields(errors)) => {
+                let fields: Vec<Option<&str>> =
+                    errors.iter().map(|error| error.field.as_deref()).collect();
+                assert_eq!(fields, vec![Some("rating"), None]);
+            }
+            _ => panic!("the form should be invalid"),
+        }
+    }
+
+    fn wine_criteria(query: serde_json::Value) -> WineCriteria {
+        serde_json::from_value(query).unwrap()
+    }
+
+    #[test]
+    fn test_page_criteria() {
+        assert_eq!(
+            page_criteria(&wine_criteria(json!({}))).unwrap(),
+            (1, DEFAULT_PER_PAGE)
+        );
+        assert_eq!(
+            page_criteria(&wine_criteria(json!({ "page": 3, "per_page": 50 }))).unwrap(),
+            (3, 50)
+        );
+        for query in vec![
+            json!({ "page": 0 }),
+            json!({ "per_page": 0 }),
+            json!({ "per_page": MAX_PER_PAGE + 1 }),
+            json!({ "page": 100_000_000_000_000_000i64, "per_page": MAX_PER_PAGE }),
+            json!({ "page": i64::MAX }),
+        ] {
+            match page_criteria(&wine_criteria(query)) {
+                Err(ApiError::Validation { .. }) => {}
+                _ => panic!("the page criteria should be invalid"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reject_unsupported_criteria() {
+        assert!(reject_unsupported_criteria(&[("q", false), ("sort", false)]).is_ok());
+        match reject_unsupported_criteria(&[("q", true), ("sort", false), ("order", true)]) {
+            Err(ApiError::InvalidFields(errors)) => {
+                let fields: Vec<Option<&str>> =
+                    errors.iter().map(|error| error.field.as_deref()).collect();
+                assert_eq!(fields, vec![Some("q"), Some("order")]);
+            }
+            _ => panic!("q and order should be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_validate_api_token_form() {
+        let form = ApiTokenForm {
+            name: " nightly crawl ".to_string(),
+            scopes: vec![ApiScopeEnum::Crawl],
+        }
+        .validate()
+        .unwrap();
+        assert_eq!(form.name, "nightly crawl");
+
+        let form = ApiTokenForm {
+            name: "  ".to_string(),
+            scopes: vec![],
+        };
+        match form.validate() {
+            Err(ApiError::InvalidFields(errors)) => {
+                let fields: Vec<Option<&str>> =
+                    errors.iter().map(|error| error.field.as_deref()).collect();
+                assert_eq!(fields, vec![Some("name"), Some("scopes")]);
+            }
+            _ => panic!("name and scopes should be invalid"),
+        }
+    }
 }
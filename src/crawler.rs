@@ -1,84 +1,470 @@
+use crate::alerts::{evaluate_wine_alerts, SaqWineChange};
+use crate::errors::{CrawlError, ParseError};
 use crate::establish_connection;
-use crate::models::{create_saq_wine, parse_wine_color};
-use crate::schema::saq_wines;
+use crate::models::{
+    create_crawl_failure, create_crawl_run, create_saq_wine_price, delist_unseen_saq_wines,
+    fail_crawl_run, finish_crawl_run, is_crawl_run_cancelled, parse_wine_color,
+    refresh_reco_matches, update_crawl_run_progress, upsert_saq_wine, CrawlRun, CrawlRunProgress,
+    NewSaqWine, SaqWine,
+};
+use crate::notifier::{deliver_pending_notifications, notifier_from_env};
+use crate::types::{CrawlStatusEnum, WineColorEnum};
+use crate::utils::env_or;
 use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDateTime;
 use diesel;
-use diesel::RunQueryDsl;
+use diesel::dsl::now;
+use diesel::pg::PgConnection;
+use diesel::{Connection, QueryResult, RunQueryDsl};
+use futures::stream::{self, StreamExt};
 use regex::Regex;
 use reqwest;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{StatusCode, Url};
 use select::document::Document;
 use select::predicate::{Attr, Class, Name, Predicate};
+use std::cmp::{max, min};
+use std::env;
+use std::panic;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::time::delay_for;
 
-async fn get_document(url: &str) -> Result<String, reqwest::Error> {
-    let client = reqwest::Client::builder().build()?;
-    let result = client.get(url).send().await?.text().await?;
-    Ok(result)
+#[cfg(test)]
+mod tests;
+
+pub fn saq_origin_url() -> String {
+    let base_url = env::var("SAQ_BASE_URL").unwrap_or_else(|_| String::from("https://www.saq.com"));
+    format!("{}/en/products/wine", base_url.trim_end_matches('/'))
+}
+
+const MAX_RETRIES: u32 = 10;
+// unless saq.com asks for longer with Retry-After
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+pub struct CrawlConfig {
+    // detail pages fetched at the same time
+    pub concurrency: usize,
+    // 0 means no limit
+    pub requests_per_second: u32,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    // doubled after every failed attempt
+    pub initial_backoff: Duration,
+}
+
+impl CrawlConfig {
+    pub fn from_env() -> CrawlConfig {
+        CrawlConfig {
+            concurrency: max(env_or("CRAWL_CONCURRENCY", 4), 1),
+            requests_per_second: env_or("CRAWL_REQUESTS_PER_SECOND", 4),
+            timeout: Duration::from_secs(env_or("CRAWL_TIMEOUT_SECONDS", 30)),
+            max_retries: min(env_or("CRAWL_MAX_RETRIES", 3), MAX_RETRIES),
+            initial_backoff: Duration::from_millis(env_or("CRAWL_BACKOFF_MILLISECONDS", 500)),
+        }
+    }
+}
+
+// one client for the whole crawl so connections to saq.com are reused.
+pub struct SaqClient {
+    client: reqwest::Client,
+    config: CrawlConfig,
+    next_request_at: Mutex<Instant>,
+}
+
+impl SaqClient {
+    pub fn new(config: CrawlConfig) -> SaqClient {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .pool_max_idle_per_host(config.concurrency)
+            .build()
+            .expect("Error building the http client");
+        SaqClient {
+            client: client,
+            config: config,
+            next_request_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    // waits for our turn so we stay under the requests per second cap.
+    async fn throttle(&self) {
+        if self.config.requests_per_second == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(1) / self.config.requests_per_second;
+        let wait = {
+            let mut next_request_at = self.next_request_at.lock().unwrap();
+            let current_time = Instant::now();
+            let request_at = max(*next_request_at, current_time);
+            *next_request_at = request_at + interval;
+            request_at - current_time
+        };
+        if wait > Duration::from_secs(0) {
+            delay_for(wait).await;
+        }
+    }
+
+    async fn get_document(&self, url: &str) -> Result<String, CrawlError> {
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+            let mut retry_after = None;
+            let message = match self.client.get(url).send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() {
+                        match response.text().await {
+                            Ok(body) => return Ok(body),
+                            Err(error) => error.to_string(),
+                        }
+                    } else if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                        retry_after = response
+                            .headers()
+                            .get(RETRY_AFTER)
+                            .and_then(|value| value.to_str().ok())
+                            .and_then(|value| value.parse::<u64>().ok())
+                            .map(Duration::from_secs);
+                        format!("HTTP status {}", status)
+                    } else {
+                        return Err(CrawlError::FetchError {
+                            url: String::from(url),
+                            message: format!("HTTP status {}", status),
+                        });
+                    }
+                }
+                Err(error) => {
+                    if !error.is_timeout() && !error.is_connect() {
+                        return Err(CrawlError::FetchError {
+                            url: String::from(url),
+                            message: error.to_string(),
+                        });
+                    }
+                    error.to_string()
+                }
+            };
+            if attempt >= self.config.max_retries {
+                return Err(CrawlError::FetchError {
+                    url: String::from(url),
+                    message: message,
+                });
+            }
+            let backoff = max(
+                backoff_delay(self.config.initial_backoff, attempt),
+                retry_after.unwrap_or_default(),
+            );
+            println!("Retrying {} in {:?} ({})", url, backoff, message);
+            delay_for(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    async fn fetch_document(&self, url: &str) -> Result<Document, CrawlError> {
+        let body = self.get_document(url).await?;
+        Ok(Document::from(&*body))
+    }
+
+    async fn fetch_saq_wine(&self, detail_page_url: &str) -> Result<SaqWineDetails, CrawlError> {
+        let document = self.fetch_document(detail_page_url).await?;
+        Ok(parse_saq_wine(&document, detail_page_url)?)
+    }
+
+    // fetches the given detail pages, at most `concurrency` at a time.
+    async fn fetch_saq_wines(
+        &self,
+        detail_page_urls: Vec<String>,
+    ) -> Vec<(String, Result<SaqWineDetails, CrawlError>)> {
+        stream::iter(detail_page_urls)
+            .map(|detail_page_url| async move {
+                let result = self.fetch_saq_wine(&detail_page_url).await;
+                (detail_page_url, result)
+            })
+            .buffer_unordered(self.config.concurrency)
+            .collect()
+            .await
+    }
+}
+
+// doubles with every attempt, up to MAX_BACKOFF.
+fn backoff_delay(initial_backoff: Duration, attempt: u32) -> Duration {
+    initial_backoff
+        .checked_mul(2u32.saturating_pow(attempt))
+        .map_or(MAX_BACKOFF, |backoff| min(backoff, MAX_BACKOFF))
 }
 
-pub async fn crawl_saq(origin_url: &String) {
+// starts a crawl in the background, returns None if one is already running.
+pub fn start_crawl(connection: &PgConnection) -> QueryResult<Option<CrawlRun>> {
+    let crawl_run = match create_crawl_run(connection)? {
+        Some(crawl_run) => crawl_run,
+        None => return Ok(None),
+    };
+    let crawl_run_id = crawl_run.id;
+    thread::spawn(move || {
+        let crawled = panic::catch_unwind(|| {
+            let mut rt = Runtime::new().expect("Error creating the crawl runtime");
+            let origin_url = saq_origin_url();
+            rt.block_on(crawl_saq(crawl_run_id, &origin_url))
+        });
+        // a run left running would keep every other crawl from starting
+        match crawled {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => {
+                println!("Crawl {} failed: {}", crawl_run_id, error);
+                abandon_crawl_run(crawl_run_id, &error.to_string());
+            }
+            Err(_) => abandon_crawl_run(crawl_run_id, "The crawl stopped unexpectedly."),
+        }
+        // notifiers may block, so they run once the crawl runtime is done.
+        match deliver_pending_notifications(&establish_connection(), &*notifier_from_env()) {
+            Ok(delivered) => println!("{} notifications were delivered", delivered),
+            Err(error) => println!("Error delivering notifications: {}", error),
+        }
+    });
+    Ok(Some(crawl_run))
+}
+
+// marks the run as failed on a connection of its own, the crawl's may be what broke.
+fn abandon_crawl_run(crawl_run_id: i32, error: &str) {
+    let database_url = env::var("DATABASE_URL").unwrap_or_default();
+    let result = PgConnection::establish(&database_url)
+        .map_err(|error| error.to_string())
+        .and_then(|connection| {
+            fail_crawl_run(&connection, crawl_run_id, error).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        println!(
+            "Error marking crawl {} as failed, it stays running until the next restart: {}",
+            crawl_run_id, error
+        );
+    }
+}
+
+// errors are those of the database, crawl errors are recorded on the run itself.
+pub async fn crawl_saq(crawl_run_id: i32, origin_url: &String) -> QueryResult<()> {
     let connection = establish_connection();
-    // delete all previous saq_wines present
-    diesel::delete(saq_wines::table)
-        .execute(&connection)
-        .expect("Error deleting saq_wines");
-    let mut document = Document::from(&*get_document(origin_url).await.unwrap());
-    let mut next_page = get_next_page(&document);
-    while next_page.is_some() {
-        for node in document.find(
+    let client = SaqClient::new(CrawlConfig::from_env());
+    let mut progress = CrawlRunProgress {
+        pages_visited: 0,
+        wines_added: 0,
+        wines_updated: 0,
+        wines_failed: 0,
+    };
+    let mut changes = Vec::new();
+    match crawl_saq_pages(
+        &connection,
+        &client,
+        crawl_run_id,
+        origin_url,
+        &mut progress,
+        &mut changes,
+    )
+    .await
+    {
+        Ok(()) => {
+            finish_crawl_run(&connection, crawl_run_id, CrawlStatusEnum::Succeeded, None)?;
+            println!("Success ! :)");
+        }
+        Err(CrawlError::Cancelled) => {
+            println!("Crawl {} was cancelled", crawl_run_id);
+            finish_crawl_run(&connection, crawl_run_id, CrawlStatusEnum::Cancelled, None)?;
+        }
+        Err(error) => {
+            println!("Crawl {} failed: {}", crawl_run_id, error);
+            finish_crawl_run(
+                &connection,
+                crawl_run_id,
+                CrawlStatusEnum::Failed,
+                Some(error.to_string()),
+            )?;
+        }
+    }
+    // wines saved before a failure or a cancellation still count
+    let matched = refresh_reco_matches(&connection)?;
+    println!("{} recommendation matches were found", matched);
+    let notified = evaluate_wine_alerts(&connection, &changes)?;
+    println!("{} notifications were queued", notified);
+    Ok(())
+}
+
+async fn crawl_saq_pages(
+    connection: &PgConnection,
+    client: &SaqClient,
+    crawl_run_id: i32,
+    origin_url: &String,
+    progress: &mut CrawlRunProgress,
+    changes: &mut Vec<SaqWineChange>,
+) -> Result<(), CrawlError> {
+    // use the database clock, last_seen_at is set by it.
+    let crawl_started_at = diesel::select(now).get_result::<NaiveDateTime>(connection)?;
+    let mut page_url = origin_url.clone();
+    let mut document = client.fetch_document(&page_url).await?;
+    loop {
+        let detail_page_urls = get_product_urls(&document, &page_url);
+        for (detail_page_url, result) in client.fetch_saq_wines(detail_page_urls).await {
+            // only failures of the page itself are recorded, the database failing stops the crawl
+            let result = match result {
+                Ok(saq_wine_details) => Ok(save_saq_wine(connection, &saq_wine_details)?),
+                Err(error) => Err(error),
+            };
+            match result {
+                Ok((_, None)) => progress.wines_added += 1,
+                Ok((saq_wine, Some(previous_saq_wine))) => {
+                    progress.wines_updated += 1;
+                    changes.extend(SaqWineChange::between(&previous_saq_wine, &saq_wine));
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    progress.wines_failed += 1;
+                    create_crawl_failure(
+                        connection,
+                        crawl_run_id,
+                        &detail_page_url,
+                        &error.to_string(),
+                    )?;
+                }
+            }
+        }
+        progress.pages_visited += 1;
+        update_crawl_run_progress(connection, crawl_run_id, progress)?;
+        if is_crawl_run_cancelled(connection, crawl_run_id)? {
+            return Err(CrawlError::Cancelled);
+        }
+        match get_next_page(&document, &page_url) {
+            Some(next_page) => {
+                document = client.fetch_document(&next_page).await?;
+                page_url = next_page;
+            }
+            None => break,
+        }
+    }
+    // the whole catalogue was visited, anything we did not see is no longer sold.
+    let delisted = delist_unseen_saq_wines(connection, &crawl_started_at)?;
+    println!("{} SAQ Wines were delisted", delisted);
+    Ok(())
+}
+
+fn get_product_urls(document: &Document, page_url: &str) -> Vec<String> {
+    document
+        .find(
             Name("div")
                 .and(Class("product-item-info"))
                 .descendant(Name("a").and(Class("product-item-photo"))),
-        ) {
-            crawl_saq_wine(node.attr("href").unwrap()).await;
-        }
-        document = Document::from(&*get_document(&next_page.unwrap()).await.unwrap());
-        next_page = get_next_page(&document);
-    }
-    println!("Success ! :)");
+        )
+        .filter_map(|node| node.attr("href"))
+        .filter_map(|href| resolve_url(page_url, href))
+        .collect()
 }
 
-fn get_next_page(document: &Document) -> Option<String> {
-    let next_page = document.find(Name("a").and(Class("next"))).next();
+fn get_next_page(document: &Document, page_url: &str) -> Option<String> {
     // there may not be a next page
-    if next_page.is_none() {
-        return None;
+    document
+        .find(Name("a").and(Class("next")))
+        .next()
+        .and_then(|node| node.attr("href"))
+        .and_then(|href| resolve_url(page_url, href))
+}
+
+// links are absolute on saq.com but may be relative on a stand-in server.
+fn resolve_url(page_url: &str, href: &str) -> Option<String> {
+    let url = Url::parse(page_url).ok()?.join(href).ok()?;
+    Some(url.to_string())
+}
+
+pub struct SaqWineDetails {
+    pub saq_code: String,
+    pub name: String,
+    pub country: String,
+    pub region: String,
+    pub designation_of_origin: String,
+    pub regulated_designation: bool,
+    pub producer: String,
+    // in milliliters
+    pub volume: BigDecimal,
+    pub price: BigDecimal,
+    pub alcohol_percent: BigDecimal,
+    pub color: WineColorEnum,
+    pub grape_varieties: Vec<String>,
+    pub available_online: bool,
+    pub url: String,
+}
+
+impl SaqWineDetails {
+    pub fn as_new_saq_wine(&self) -> NewSaqWine {
+        NewSaqWine {
+            saq_code: &self.saq_code,
+            name: &self.name,
+            country: &self.country,
+            region: &self.region,
+            designation_of_origin: &self.designation_of_origin,
+            regulated_designation: &self.regulated_designation,
+            producer: &self.producer,
+            volume: &self.volume,
+            price: &self.price,
+            alcohol_percent: &self.alcohol_percent,
+            color: &self.color,
+            grape_varieties: &self.grape_varieties,
+            available_online: &self.available_online,
+            url: &self.url,
+        }
     }
-    Some(String::from(next_page.unwrap().attr("href").unwrap()))
 }
 
-async fn crawl_saq_wine(detail_page_url: &str) {
-    let connection = establish_connection();
-    let response = get_document(detail_page_url);
-    let result = response.await;
-    if result.is_err() {
-        println!("There was an error fetching wine {}", detail_page_url);
-        return;
+fn save_saq_wine(
+    connection: &PgConnection,
+    saq_wine_details: &SaqWineDetails,
+) -> QueryResult<(SaqWine, Option<SaqWine>)> {
+    let (saq_wine, previous_saq_wine) =
+        upsert_saq_wine(connection, &saq_wine_details.as_new_saq_wine())?;
+    println!(
+        "SAQ Wine: {} ({}) was saved",
+        saq_wine_details.name, saq_wine_details.saq_code
+    );
+    // only price changes are kept in the history
+    let price_changed = previous_saq_wine
+        .as_ref()
+        .map_or(true, |previous_saq_wine| {
+            previous_saq_wine.price != saq_wine.price
+        });
+    if price_changed {
+        create_saq_wine_price(connection, saq_wine.id, &saq_wine.price)?;
     }
-    let document = Document::from(&*result.unwrap());
+    Ok((saq_wine, previous_saq_wine))
+}
+
+fn parse_saq_wine(
+    document: &Document,
+    detail_page_url: &str,
+) -> Result<SaqWineDetails, ParseError> {
+    let parse_error = |field: &'static str, reason: &str| ParseError {
+        url: String::from(detail_page_url),
+        field: field,
+        reason: String::from(reason),
+    };
 
     let name = document
         .find(Name("h1").and(Class("page-title")))
         .next()
-        .unwrap()
-        .text();
-    let price = parse_price(&document);
+        .map(|node| String::from(node.text().trim()))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| parse_error("name", "missing page title"))?;
+    let price = parse_price(document).ok_or_else(|| parse_error("price", "missing final price"))?;
+    let saq_code = parse_saq_code(document, detail_page_url)
+        .ok_or_else(|| parse_error("saq_code", "missing SAQ code"))?;
 
-    // fetch info from detailed info in the web page.
-    let country = parse_wine_info(&document, "Country").unwrap();
-
-    let mut region = String::from("");
-    let region_option = parse_wine_info(&document, "Region");
-    if region_option.is_some() {
-        region = parse_wine_info(&document, "Region").unwrap();
-    }
+    // fetch info from detailed info in the web page, some products (gift boxes, accessories...)
+    // do not list everything so only what we cannot do without is required.
+    let country = parse_wine_info(document, "Country").unwrap_or_default();
+    let region = parse_wine_info(document, "Region").unwrap_or_default();
+    let producer = parse_wine_info(document, "Producer").unwrap_or_default();
 
     let available_online = document.find(Class("out-of-stock-online")).next().is_none();
 
     let mut designation_of_origin = String::from("");
-    let designation_of_origin_option = parse_wine_info(&document, "Designation of origin");
+    let designation_of_origin_option = parse_wine_info(document, "Designation of origin");
 
-    let regulated_designation_option = parse_wine_info(&document, "Regulated Designation");
+    let regulated_designation_option = parse_wine_info(document, "Regulated Designation");
     let regulated_designation = designation_of_origin_option.is_some()
         && regulated_designation_option.is_some()
         && regulated_designation_option.unwrap() != "Table wine";
@@ -86,54 +472,44 @@ async fn crawl_saq_wine(detail_page_url: &str) {
         designation_of_origin = designation_of_origin_option.unwrap();
     }
 
-    let producer = parse_wine_info(&document, "Producer").unwrap();
-
-    let volume_node = document.find(Attr("data-th", "Size")).next().unwrap();
-    let volume_text = volume_node.text();
-    let volume;
-    if volume_text.contains("ml") {
-        volume = volume_text[..volume_text.find("ml").unwrap()]
-            .trim()
-            .to_string();
-    } else {
-        let volume_liters =
-            BigDecimal::from_str(&volume_text[..volume_text.find("L").unwrap()].trim()).unwrap();
-        volume = (volume_liters * BigDecimal::from_str("1000").unwrap())
-            .to_u32()
-            .unwrap()
-            .to_string();
-    }
-
-    let alcohol_percent_option = document.find(Attr("data-th", "Degree of alcohol")).next();
-    let alcohol_percent: BigDecimal;
-    if alcohol_percent_option.is_some() {
-        let alcohol_percent_text = alcohol_percent_option.unwrap().text();
-        alcohol_percent = BigDecimal::from_str(
-            alcohol_percent_text[..alcohol_percent_text.find("%").unwrap()].trim(),
-        )
-        .unwrap();
-    } else {
-        alcohol_percent = BigDecimal::from(0);
-    }
+    let volume_text =
+        parse_wine_info(document, "Size").ok_or_else(|| parse_error("volume", "missing size"))?;
+    let volume = parse_volume(&volume_text)
+        .ok_or_else(|| parse_error("volume", &format!("unrecognized size {}", volume_text)))?;
 
-    let wine_color = parse_wine_info(&document, "Color").unwrap();
+    let alcohol_percent = match parse_wine_info(document, "Degree of alcohol") {
+        Some(alcohol_percent_text) => {
+            parse_alcohol_percent(&alcohol_percent_text).ok_or_else(|| {
+                parse_error(
+                    "alcohol_percent",
+                    &format!("unrecognized degree of alcohol {}", alcohol_percent_text),
+                )
+            })?
+        }
+        None => BigDecimal::from(0),
+    };
 
-    create_saq_wine(
-        &connection,
-        &name.trim(),
-        &country,
-        &region,
-        &designation_of_origin,
-        &regulated_designation,
-        &producer,
-        &BigDecimal::from_str(&volume).unwrap(),
-        &BigDecimal::from_str(&price).unwrap(),
-        &alcohol_percent,
-        &parse_wine_color(&wine_color.to_lowercase()).unwrap(),
-        &parse_grape_varieties(&document),
-        &available_online,
-    );
-    println!("SAQ Wine: {} was added", name.trim());
+    let wine_color =
+        parse_wine_info(document, "Color").ok_or_else(|| parse_error("color", "missing color"))?;
+    let color = parse_wine_color(&wine_color.to_lowercase())
+        .map_err(|_| parse_error("color", &format!("unrecognized color {}", wine_color)))?;
+
+    Ok(SaqWineDetails {
+        saq_code: saq_code,
+        name: name,
+        country: country,
+        region: region,
+        designation_of_origin: designation_of_origin,
+        regulated_designation: regulated_designation,
+        producer: producer,
+        volume: volume,
+        price: price,
+        alcohol_percent: alcohol_percent,
+        color: color,
+        grape_varieties: parse_grape_varieties(document),
+        available_online: available_online,
+        url: String::from(detail_page_url),
+    })
 }
 
 fn parse_wine_info(document: &Document, info_selector: &str) -> Option<String> {
@@ -144,15 +520,52 @@ fn parse_wine_info(document: &Document, info_selector: &str) -> Option<String> {
     None
 }
 
-fn parse_price(document: &Document) -> String {
-    String::from(
-        document
-            .find(Attr("data-price-type", "finalPrice"))
-            .next()
-            .unwrap()
-            .attr("data-price-amount")
-            .unwrap(),
-    )
+fn parse_saq_code(document: &Document, detail_page_url: &str) -> Option<String> {
+    let saq_code = parse_wine_info(document, "SAQ code");
+    if saq_code.is_some() {
+        return saq_code;
+    }
+    // product urls end with the SAQ code, e.g. https://www.saq.com/en/10324623
+    let last_segment = detail_page_url
+        .split('?')
+        .next()
+        .unwrap()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap();
+    if !last_segment.is_empty() && last_segment.chars().all(|c| c.is_ascii_digit()) {
+        return Some(String::from(last_segment));
+    }
+    None
+}
+
+fn parse_price(document: &Document) -> Option<BigDecimal> {
+    document
+        .find(Attr("data-price-type", "finalPrice"))
+        .next()
+        .and_then(|node| node.attr("data-price-amount"))
+        .and_then(|price| BigDecimal::from_str(price.trim()).ok())
+}
+
+// e.g. "750 ml", "1.5 L" or "1,5 L", in milliliters.
+fn parse_volume(volume_text: &str) -> Option<BigDecimal> {
+    let volume_text = volume_text.to_lowercase().replace(",", ".");
+    if let Some(index) = volume_text.find("ml") {
+        return BigDecimal::from_str(volume_text[..index].trim()).ok();
+    }
+    let index = volume_text.find('l')?;
+    let volume_liters = BigDecimal::from_str(volume_text[..index].trim()).ok()?;
+    (volume_liters * BigDecimal::from(1000))
+        .to_u32()
+        .map(BigDecimal::from)
+}
+
+// e.g. "13.5 %" or "13,5 %"
+fn parse_alcohol_percent(alcohol_percent_text: &str) -> Option<BigDecimal> {
+    let alcohol_percent_text = alcohol_percent_text.replace(",", ".");
+    let index = alcohol_percent_text.find('%')?;
+    BigDecimal::from_str(alcohol_percent_text[..index].trim()).ok()
 }
 
 fn parse_grape_varieties(document: &Document) -> Vec<String> {
@@ -0,0 +1,380 @@
+use super::*;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+fn fixture(path: &str) -> Option<&'static str> {
+    match path {
+        "/en/products/wine" => Some(include_str!("fixtures/listing_1.html")),
+        "/en/products/wine?p=2" => Some(include_str!("fixtures/listing_2.html")),
+        "/en/10324623" => Some(include_str!("fixtures/detail_10324623.html")),
+        "/en/13467048" => Some(include_str!("fixtures/detail_13467048.html")),
+        "/en/11766597" => Some(include_str!("fixtures/detail_11766597.html")),
+        "/en/14099363" => Some(include_str!("fixtures/detail_14099363.html")),
+        _ => None,
+    }
+}
+
+// serves the saved SAQ pages on a local port, returns its base url.
+fn start_saq_stand_in() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut flaky_requests = 0;
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // skip the request headers
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+            }
+            let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+            // the first request to this page fails, like saq.com does when overloaded
+            if path == "/en/flaky/10324623" {
+                flaky_requests += 1;
+                if flaky_requests == 1 {
+                    let response = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                    stream.write_all(response.as_bytes()).unwrap();
+                    continue;
+                }
+            }
+            let response = match fixture(&path.replacen("/flaky", "", 1)) {
+                Some(body) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                None => String::from(
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                ),
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    format!("http://{}", address)
+}
+
+fn test_client(max_retries: u32) -> SaqClient {
+    SaqClient::new(CrawlConfig {
+        concurrency: 2,
+        requests_per_second: 0,
+        timeout: Duration::from_secs(5),
+        max_retries: max_retries,
+        initial_backoff: Duration::from_millis(10),
+    })
+}
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+#[test]
+fn test_saq_origin_url() {
+    env::set_var("SAQ_BASE_URL", "http://127.0.0.1:8081/");
+    assert_eq!(saq_origin_url(), "http://127.0.0.1:8081/en/products/wine");
+    env::remove_var("SAQ_BASE_URL");
+    assert_eq!(saq_origin_url(), "https://www.saq.com/en/products/wine");
+}
+
+#[test]
+fn test_backoff_delay() {
+    let initial_backoff = Duration::from_millis(500);
+    assert_eq!(backoff_delay(initial_backoff, 0), initial_backoff);
+    assert_eq!(backoff_delay(initial_backoff, 3), Duration::from_secs(4));
+    assert_eq!(backoff_delay(initial_backoff, 20), MAX_BACKOFF);
+    assert_eq!(backoff_delay(initial_backoff, 40), MAX_BACKOFF);
+    assert_eq!(backoff_delay(Duration::from_secs(u64::MAX), 1), MAX_BACKOFF);
+}
+
+#[test]
+fn test_parse_price() {
+    let document = Document::from(include_str!("fixtures/detail_10324623.html"));
+    assert_eq!(parse_price(&document), Some(decimal("17.95")));
+    assert_eq!(parse_price(&Document::from("<html></html>")), None);
+}
+
+#[test]
+fn test_parse_volume() {
+    assert_eq!(parse_volume("750 ml"), Some(decimal("750")));
+    assert_eq!(parse_volume("375 mL"), Some(decimal("375")));
+    assert_eq!(parse_volume("1.5 L"), Some(decimal("1500")));
+    assert_eq!(parse_volume("1,5 L"), Some(decimal("1500")));
+    assert_eq!(parse_volume("3 x 375 ml"), None);
+    assert_eq!(parse_volume("Gift box"), None);
+}
+
+#[test]
+fn test_parse_alcohol_percent() {
+    assert_eq!(parse_alcohol_percent("14.5 %"), Some(decimal("14.5")));
+    assert_eq!(parse_alcohol_percent("12,5 %"), Some(decimal("12.5")));
+    assert_eq!(parse_alcohol_percent("12.5"), None);
+}
+
+#[test]
+fn test_parse_wine_info() {
+    let document = Document::from(include_str!("fixtures/detail_10324623.html"));
+    assert_eq!(
+        parse_wine_info(&document, "Region"),
+        Some(String::from("Vallée du Rhône"))
+    );
+    assert_eq!(parse_wine_info(&document, "Sugar content"), None);
+}
+
+#[test]
+fn test_parse_grape_varieties() {
+    let document = Document::from(include_str!("fixtures/detail_10324623.html"));
+    assert_eq!(
+        parse_grape_varieties(&document),
+        vec!["Grenache", "Syrah", "Mourvèdre"]
+    );
+    let document = Document::from(include_str!("fixtures/detail_11766597.html"));
+    assert_eq!(
+        parse_grape_varieties(&document),
+        vec!["Grenache", "Cinsault"]
+    );
+    let document = Document::from(include_str!("fixtures/detail_14099363.html"));
+    assert_eq!(parse_grape_varieties(&document), Vec::<String>::new());
+}
+
+#[test]
+fn test_parse_saq_code() {
+    let document = Document::from(include_str!("fixtures/detail_10324623.html"));
+    assert_eq!(
+        parse_saq_code(&document, "https://www.saq.com/en/10324623"),
+        Some(String::from("10324623"))
+    );
+    // falls back on the product url
+    let document = Document::from("<html></html>");
+    assert_eq!(
+        parse_saq_code(&document, "https://www.saq.com/en/11766597/?p=1"),
+        Some(String::from("11766597"))
+    );
+    assert_eq!(
+        parse_saq_code(&document, "https://www.saq.com/en/products/wine"),
+        None
+    );
+}
+
+#[test]
+fn test_get_next_page() {
+    let origin_url = "https://www.saq.com/en/products/wine";
+    let document = Document::from(include_str!("fixtures/listing_1.html"));
+    assert_eq!(
+        get_next_page(&document, origin_url),
+        Some(String::from("https://www.saq.com/en/products/wine?p=2"))
+    );
+    let document = Document::from(include_str!("fixtures/listing_2.html"));
+    assert_eq!(get_next_page(&document, origin_url), None);
+}
+
+#[actix_rt::test]
+async fn test_crawl_listing_pages() {
+    let base_url = start_saq_stand_in();
+    let client = test_client(0);
+    let origin_url = format!("{}/en/products/wine", base_url);
+
+    let document = client.fetch_document(&origin_url).await.unwrap();
+    assert_eq!(
+        get_product_urls(&document, &origin_url),
+        vec![
+            format!("{}/en/10324623", base_url),
+            format!("{}/en/13467048", base_url),
+        ]
+    );
+    let next_page = get_next_page(&document, &origin_url).unwrap();
+    assert_eq!(next_page, format!("{}/en/products/wine?p=2", base_url));
+
+    let document = client.fetch_document(&next_page).await.unwrap();
+    assert_eq!(
+        get_product_urls(&document, &next_page),
+        vec![
+            format!("{}/en/11766597", base_url),
+            format!("{}/en/14099363", base_url),
+        ]
+    );
+    assert_eq!(get_next_page(&document, &next_page), None);
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wine() {
+    let base_url = start_saq_stand_in();
+    let saq_wine = test_client(0)
+        .fetch_saq_wine(&format!("{}/en/10324623", base_url))
+        .await
+        .unwrap();
+    assert_eq!(
+        saq_wine.as_new_saq_wine(),
+        NewSaqWine {
+            saq_code: "10324623",
+            name: "Domaine de la Janasse Côtes du Rhône 2018",
+            country: "France",
+            region: "Vallée du Rhône",
+            designation_of_origin: "Côtes-du-rhône",
+            regulated_designation: &true,
+            producer: "Domaine de la Janasse",
+            volume: &decimal("750"),
+            price: &decimal("17.95"),
+            alcohol_percent: &decimal("14.5"),
+            color: &WineColorEnum::Red,
+            grape_varieties: &vec![
+                String::from("Grenache"),
+                String::from("Syrah"),
+                String::from("Mourvèdre"),
+            ],
+            available_online: &true,
+            url: &format!("{}/en/10324623", base_url),
+        }
+    );
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wine_magnum_out_of_stock() {
+    let base_url = start_saq_stand_in();
+    let saq_wine = test_client(0)
+        .fetch_saq_wine(&format!("{}/en/13467048", base_url))
+        .await
+        .unwrap();
+    assert_eq!(
+        saq_wine.as_new_saq_wine(),
+        NewSaqWine {
+            saq_code: "13467048",
+            name: "Pierre Sparr Pinot Blanc Magnum",
+            country: "France",
+            region: "Alsace",
+            // table wines do not keep their designation of origin
+            designation_of_origin: "",
+            regulated_designation: &false,
+            producer: "Pierre Sparr",
+            volume: &decimal("1500"),
+            price: &decimal("39.25"),
+            alcohol_percent: &decimal("12.5"),
+            color: &WineColorEnum::White,
+            grape_varieties: &vec![String::from("Pinot blanc")],
+            available_online: &false,
+            url: &format!("{}/en/13467048", base_url),
+        }
+    );
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wine_missing_details() {
+    let base_url = start_saq_stand_in();
+    let saq_wine = test_client(0)
+        .fetch_saq_wine(&format!("{}/en/11766597", base_url))
+        .await
+        .unwrap();
+    assert_eq!(
+        saq_wine.as_new_saq_wine(),
+        NewSaqWine {
+            saq_code: "11766597",
+            name: "Mas des Bressades Rosé",
+            country: "France",
+            region: "Vallée du Rhône",
+            designation_of_origin: "",
+            regulated_designation: &false,
+            producer: "Mas des Bressades",
+            volume: &decimal("750"),
+            price: &decimal("15.60"),
+            alcohol_percent: &decimal("0"),
+            color: &WineColorEnum::Pink,
+            grape_varieties: &vec![String::from("Grenache"), String::from("Cinsault")],
+            available_online: &true,
+            url: &format!("{}/en/11766597", base_url),
+        }
+    );
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wine_parse_error() {
+    let base_url = start_saq_stand_in();
+    let client = test_client(0);
+    let url = format!("{}/en/14099363", base_url);
+    match client.fetch_saq_wine(&url).await {
+        Err(CrawlError::ParseError(error)) => assert_eq!(
+            error,
+            ParseError {
+                url: url.clone(),
+                field: "color",
+                reason: String::from("missing color"),
+            }
+        ),
+        _ => panic!("the gift box should not be parsed"),
+    }
+    match client
+        .fetch_saq_wine(&format!("{}/en/00000000", base_url))
+        .await
+    {
+        Err(CrawlError::FetchError { .. }) => (),
+        _ => panic!("missing pages should not be parsed"),
+    }
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wine_retries() {
+    let base_url = start_saq_stand_in();
+    let url = format!("{}/en/flaky/10324623", base_url);
+    match test_client(0).fetch_saq_wine(&url).await {
+        Err(CrawlError::FetchError { message, .. }) => {
+            assert_eq!(message, "HTTP status 503 Service Unavailable")
+        }
+        _ => panic!("the first request should fail"),
+    }
+
+    let base_url = start_saq_stand_in();
+    let url = format!("{}/en/flaky/10324623", base_url);
+    let saq_wine = test_client(1).fetch_saq_wine(&url).await.unwrap();
+    assert_eq!(saq_wine.saq_code, "10324623");
+}
+
+#[actix_rt::test]
+async fn test_fetch_saq_wines() {
+    let base_url = start_saq_stand_in();
+    let detail_page_urls = vec![
+        format!("{}/en/10324623", base_url),
+        format!("{}/en/13467048", base_url),
+        format!("{}/en/11766597", base_url),
+        format!("{}/en/14099363", base_url),
+    ];
+    let mut results = test_client(0)
+        .fetch_saq_wines(detail_page_urls.clone())
+        .await;
+    // pages are fetched concurrently so they may come back in any order
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    let saq_codes: Vec<Option<String>> = results
+        .into_iter()
+        .map(|(_, result)| result.ok().map(|saq_wine| saq_wine.saq_code))
+        .collect();
+    assert_eq!(
+        saq_codes,
+        vec![
+            Some(String::from("10324623")),
+            Some(String::from("11766597")),
+            Some(String::from("13467048")),
+            None,
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn test_requests_per_second() {
+    let base_url = start_saq_stand_in();
+    let client = SaqClient::new(CrawlConfig {
+        concurrency: 4,
+        requests_per_second: 20,
+        timeout: Duration::from_secs(5),
+        max_retries: 0,
+        initial_backoff: Duration::from_millis(10),
+    });
+    let started_at = Instant::now();
+    let results = client
+        .fetch_saq_wines(vec![format!("{}/en/10324623", base_url); 5])
+        .await;
+    assert_eq!(results.len(), 5);
+    // the first request goes out right away, the four others 50ms apart
+    assert!(started_at.elapsed() >= Duration::from_millis(200));
+}
@@ -0,0 +1,42 @@
+use crate::errors::{ApiError, DbError};
+use crate::utils::env_or;
+use actix_web::error::BlockingError;
+use actix_web::web;
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::QueryResult;
+use std::env;
+use std::time::Duration;
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+pub fn create_pool() -> DbPool {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    Pool::builder()
+        .max_size(env_or("DATABASE_POOL_SIZE", 10))
+        .connection_timeout(Duration::from_secs(env_or(
+            "DATABASE_POOL_TIMEOUT_SECONDS",
+            5,
+        )))
+        .build(ConnectionManager::<PgConnection>::new(database_url))
+        .expect("Error creating the database pool")
+}
+
+// diesel blocks, so queries run on the blocking thread pool instead of the actix workers.
+pub async fn run<F, T>(pool: &DbPool, query: F) -> Result<T, ApiError>
+where
+    F: FnOnce(&PgConnection) -> QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    web::block(move || -> Result<T, DbError> {
+        let conn = pool.get().map_err(DbError::PoolError)?;
+        query(&conn).map_err(DbError::QueryError)
+    })
+    .await
+    .map_err(|error| match error {
+        BlockingError::Error(error) => ApiError::from(error),
+        // the query panicked
+        BlockingError::Canceled => ApiError::Internal,
+    })
+}
@@ -1,4 +1,5 @@
 use actix_web::{error, http, HttpResponse};
+use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind};
 
 #[derive(Fail, Debug)]
 pub enum LoginError {
@@ -9,7 +10,239 @@ pub enum LoginError {
 impl error::ResponseError for LoginError {
     fn error_response(&self) -> HttpResponse {
         match *self {
-            LoginError::ValidationError => HttpResponse::new(http::StatusCode::BAD_REQUEST),
+            LoginError::ValidationError => {
+                ApiError::Login(LoginError::ValidationError).error_response()
+            }
+        }
+    }
+}
+
+// one invalid field of a form, field is None when the error spans several fields.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+// every error a handler returns, rendered as {"code", "message", "field"}.
+#[derive(Fail, Debug)]
+pub enum ApiError {
+    #[fail(display = "{}", _0)]
+    Login(#[cause] LoginError),
+    #[fail(display = "{}", message)]
+    Validation {
+        field: Option<String>,
+        message: String,
+    },
+    #[fail(display = "some fields are invalid")]
+    InvalidFields(Vec<FieldError>),
+    #[fail(display = "authentication required")]
+    Unauthorized,
+    #[fail(display = "forbidden")]
+    Forbidden,
+    #[fail(display = "verify your email address first")]
+    EmailNotVerified,
+    #[fail(display = "{} not found", _0)]
+    NotFound(&'static str),
+    #[fail(display = "{}", message)]
+    Conflict {
+        field: Option<String>,
+        message: String,
+    },
+    #[fail(display = "database unavailable")]
+    Unavailable,
+    #[fail(display = "internal server error")]
+    Internal,
+}
+
+impl ApiError {
+    pub fn invalid(field: &str, message: &str) -> ApiError {
+        ApiError::Validation {
+            field: Some(field.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    // for bodies and query strings that don't deserialize
+    pub fn malformed(message: &str) -> ApiError {
+        ApiError::Validation {
+            field: None,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ApiError::Login(_) => "invalid_credentials",
+            ApiError::Validation { .. } | ApiError::InvalidFields(_) => "validation_error",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden => "forbidden",
+            ApiError::EmailNotVerified => "email_not_verified",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict { .. } => "conflict",
+            ApiError::Unavailable => "service_unavailable",
+            ApiError::Internal => "internal_error",
+        }
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            ApiError::Validation { field, .. } | ApiError::Conflict { field, .. } => {
+                field.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl error::ResponseError for ApiError {
+    fn status_code(&self) -> http::StatusCode {
+        match *self {
+            ApiError::Login(_) | ApiError::Validation { .. } | ApiError::InvalidFields(_) => {
+                http::StatusCode::BAD_REQUEST
+            }
+            ApiError::Unauthorized => http::StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::EmailNotVerified => http::StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => http::StatusCode::NOT_FOUND,
+            ApiError::Conflict { .. } => http::StatusCode::CONFLICT,
+            ApiError::Unavailable => http::StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal => http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = json!({
+            "code": self.code(),
+            "message": self.to_string(),
+            "field": self.field(),
+        });
+        if let ApiError::InvalidFields(errors) = self {
+            body["errors"] = json!(errors);
+        }
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+impl From<LoginError> for ApiError {
+    fn from(error: LoginError) -> Self {
+        ApiError::Login(error)
+    }
+}
+
+// postgres names constraints <table>_<column>_key, <table>_<column>_fkey...
+fn constraint_field(info: &dyn DatabaseErrorInformation) -> Option<String> {
+    if let Some(column_name) = info.column_name() {
+        return Some(column_name.to_string());
+    }
+    let field = info
+        .constraint_name()?
+        .trim_start_matches(info.table_name()?)
+        .trim_start_matches('_')
+        .trim_end_matches("_fkey")
+        .trim_end_matches("_pkey")
+        .trim_end_matches("_key");
+    if field.is_empty() {
+        return None;
+    }
+    Some(field.to_string())
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                let field = constraint_field(&*info);
+                ApiError::Conflict {
+                    message: format!("{} already exists", field.as_deref().unwrap_or("record")),
+                    field: field,
+                }
+            }
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                let field = constraint_field(&*info);
+                ApiError::Validation {
+                    message: format!("{} does not exist", field.as_deref().unwrap_or("record")),
+                    field: field,
+                }
+            }
+            error => {
+                println!("Database query failed: {}", error);
+                ApiError::Internal
+            }
+        }
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum CrawlError {
+    #[fail(display = "Could not fetch {}: {}", url, message)]
+    FetchError { url: String, message: String },
+    #[fail(display = "{}", _0)]
+    ParseError(#[cause] ParseError),
+    #[fail(display = "The crawl was cancelled.")]
+    Cancelled,
+    #[fail(display = "Database query failed: {}", _0)]
+    QueryError(#[cause] diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for CrawlError {
+    fn from(error: diesel::result::Error) -> Self {
+        CrawlError::QueryError(error)
+    }
+}
+
+#[derive(Fail, Debug, PartialEq)]
+#[fail(display = "Could not parse {} of {}: {}", field, url, reason)]
+pub struct ParseError {
+    pub url: String,
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl From<ParseError> for CrawlError {
+    fn from(error: ParseError) -> Self {
+        CrawlError::ParseError(error)
+    }
+}
+
+#[derive(Fail, Debug)]
+#[fail(display = "Could not notify {}: {}", email, message)]
+pub struct NotifyError {
+    pub email: String,
+    pub message: String,
+}
+
+#[derive(Fail, Debug)]
+#[fail(display = "Could not email {}: {}", to, message)]
+pub struct MailError {
+    pub to: String,
+    pub message: String,
+}
+
+impl From<MailError> for NotifyError {
+    fn from(error: MailError) -> Self {
+        NotifyError {
+            email: error.to,
+            message: error.message,
+        }
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum DbError {
+    #[fail(display = "Could not get a database connection: {}", _0)]
+    PoolError(#[cause] diesel::r2d2::PoolError),
+    #[fail(display = "Database query failed: {}", _0)]
+    QueryError(#[cause] diesel::result::Error),
+}
+
+impl From<DbError> for ApiError {
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::PoolError(error) => {
+                println!("Could not get a database connection: {}", error);
+                ApiError::Unavailable
+            }
+            DbError::QueryError(error) => ApiError::from(error),
         }
     }
 }
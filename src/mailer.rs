@@ -0,0 +1,124 @@
+use crate::errors::MailError;
+use crate::utils::env_or;
+use actix_web::error::BlockingError;
+use actix_web::web;
+use lettre::smtp::{ClientSecurity, SmtpClient};
+use lettre::Transport;
+use lettre_email::EmailBuilder;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Mailer {
+    fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+// plain SMTP without TLS, meant for a local relay or test server.
+pub struct SmtpMailer {
+    pub host: String,
+    pub port: u16,
+    pub from: String,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> SmtpMailer {
+        SmtpMailer {
+            host: env_or("SMTP_HOST", "localhost".to_string()),
+            port: env_or("SMTP_PORT", 25),
+            from: env_or("SMTP_FROM", "alerts@winecollections.ca".to_string()),
+        }
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        let mail_error = |message: String| MailError {
+            to: email.to.clone(),
+            message: message,
+        };
+        let message = EmailBuilder::new()
+            .to(email.to.as_str())
+            .from(self.from.as_str())
+            .subject(email.subject.as_str())
+            .text(email.body.as_str())
+            .build()
+            .map_err(|error| mail_error(error.to_string()))?;
+        let mut transport = SmtpClient::new((self.host.as_str(), self.port), ClientSecurity::None)
+            .map_err(|error| mail_error(error.to_string()))?
+            .transport();
+        transport
+            .send(message.into())
+            .map(|_| ())
+            .map_err(|error| mail_error(error.to_string()))
+    }
+}
+
+// appends emails to a file instead of sending them, for local testing.
+pub struct FileMailer {
+    pub path: String,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| {
+                writeln!(
+                    file,
+                    "To: {}\nSubject: {}\n\n{}\n",
+                    email.to, email.subject, email.body
+                )
+            })
+            .map_err(|error| MailError {
+                to: email.to.clone(),
+                message: error.to_string(),
+            })
+    }
+}
+
+// MAILER is one of file (default) or smtp.
+pub fn mailer_from_env() -> Box<dyn Mailer> {
+    match env::var("MAILER")
+        .unwrap_or_else(|_| "file".to_string())
+        .as_str()
+    {
+        "smtp" => Box::new(SmtpMailer::from_env()),
+        "file" => Box::new(FileMailer {
+            path: env_or("MAILER_FILE", "emails.log".to_string()),
+        }),
+        mailer => panic!("Unknown MAILER {}", mailer),
+    }
+}
+
+// where links in emails point, https://<DOMAIN> unless BASE_URL says otherwise.
+pub fn base_url() -> String {
+    env::var("BASE_URL").unwrap_or_else(|_| {
+        match env::var("DOMAIN")
+            .unwrap_or_else(|_| "localhost".to_string())
+            .as_str()
+        {
+            "localhost" => "http://localhost:8080".to_string(),
+            domain => format!("https://{}", domain),
+        }
+    })
+}
+
+// requests don't wait on the mail server, failures are only logged. mails are sent on
+// the blocking pool so a burst of requests can't start a thread each.
+pub fn send_in_background(email: Email) {
+    actix_rt::spawn(async move {
+        match web::block(move || mailer_from_env().send(&email)).await {
+            Ok(()) => {}
+            Err(BlockingError::Error(error)) => println!("{}", error),
+            Err(BlockingError::Canceled) => println!("Sending an email panicked"),
+        }
+    });
+}
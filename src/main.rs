@@ -8,21 +8,34 @@ extern crate serde_derive;
 extern crate serde_json;
 #[macro_use]
 extern crate diesel;
+mod alerts;
 mod controllers;
 mod crawler;
+mod db;
 mod errors;
+mod mailer;
 mod models;
+mod notifier;
+mod passwords;
+mod scheduler;
 mod schema;
+mod search;
 mod types;
 mod utils;
+mod validation;
+mod views;
 use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_web::*;
 use actix_web::{middleware, App, HttpServer};
 use controllers::*;
+use db::create_pool;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use models::{fail_interrupted_crawl_runs, promote_admin, session_lifetime_days};
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use scheduler::{spawn_crawl_scheduler, CrawlSchedule};
 use std::env;
+use validation::normalize_email;
 
 pub fn establish_connection() -> PgConnection {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
@@ -38,15 +51,42 @@ async fn main() -> std::io::Result<()> {
         dotenv::dotenv().ok();
     }
     let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
+    match fail_interrupted_crawl_runs(&establish_connection()) {
+        Ok(0) => {}
+        Ok(interrupted_crawl_runs) => println!(
+            "{} interrupted crawl run(s) marked as failed",
+            interrupted_crawl_runs
+        ),
+        Err(error) => println!("Error failing interrupted crawl runs: {}", error),
+    }
+    // e.g. ADMIN_EMAIL=me@example.com, once that account is registered and verified.
+    if let Ok(admin_email) = env::var("ADMIN_EMAIL") {
+        let admin_email = normalize_email(&admin_email);
+        match promote_admin(&establish_connection(), &admin_email) {
+            Ok(0) => {}
+            Ok(_) => println!("{} promoted to admin", admin_email),
+            Err(error) => println!("Error promoting {} to admin: {}", admin_email, error),
+        }
+    }
+    let pool = create_pool();
+    // e.g. CRAWL_SCHEDULE="daily 03:00", no automatic crawls when unset.
+    if let Ok(crawl_schedule) = env::var("CRAWL_SCHEDULE") {
+        spawn_crawl_scheduler(
+            CrawlSchedule::parse(&crawl_schedule)
+                .expect(&format!("Invalid CRAWL_SCHEDULE {}", crawl_schedule)),
+            pool.clone(),
+        );
+    }
     let serv = HttpServer::new(move || {
         App::new()
+            .data(pool.clone())
             .wrap(middleware::Logger::default())
             .wrap(IdentityService::new(
                 CookieIdentityPolicy::new(secret_key.as_bytes())
                     .name("auth")
                     .path("/")
                     .domain(domain.as_str())
-                    .max_age(2592000)
+                    .max_age(session_lifetime_days() * 24 * 60 * 60)
                     .secure(true),
             ))
             .service(web::resource("/").route(web::get().to(index)))
@@ -54,11 +94,69 @@ async fn main() -> std::io::Result<()> {
             .service(actix_files::Files::new("/static/css", "./static/css/").show_files_listing())
             .service(actix_files::Files::new("/static/img", "./static/img/").show_files_listing())
             .service(web::resource("/health/").route(web::get().to(get_health)))
-            .service(web::resource("/crawl/").route(web::post().to(crawl_saq_controller)))
+            .service(
+                web::resource("/crawl/")
+                    .route(web::post().to(crawl_saq_controller))
+                    .route(web::get().to(get_crawl_runs)),
+            )
+            .service(
+                web::resource("/crawl/current/").route(web::delete().to(cancel_crawl_controller)),
+            )
+            .service(web::resource("/crawl/{crawl_run_id}/").route(web::get().to(get_crawl_run)))
             .service(web::resource("/users/").route(web::post().to(register)))
+            .service(web::resource("/admin/users/").route(web::get().to(get_users)))
+            .service(
+                web::resource("/admin/users/{user_id}/")
+                    .route(web::put().to(update_user_role))
+                    .route(web::delete().to(delete_user)),
+            )
+            .service(
+                web::resource("/apitokens/")
+                    .route(web::post().to(create_api_token_controller))
+                    .route(web::get().to(get_api_tokens)),
+            )
+            .service(
+                web::resource("/apitokens/{api_token_id}/")
+                    .route(web::delete().to(delete_api_token)),
+            )
+            .service(web::resource("/users/verify/").route(web::get().to(verify_email)))
+            .service(
+                web::resource("/users/verification/")
+                    .route(web::post().to(resend_verification_email)),
+            )
+            .service(
+                web::resource("/alerts/")
+                    .route(web::post().to(create_alert))
+                    .route(web::get().to(get_alerts)),
+            )
+            .service(
+                web::resource("/alerts/{wine_alert_id}/").route(web::delete().to(delete_alert)),
+            )
+            .service(web::resource("/notifications/").route(web::get().to(get_notifications)))
+            .service(
+                web::resource("/notifications/{notification_id}/read/")
+                    .route(web::post().to(read_notification)),
+            )
+            .service(web::resource("/users/me/password/").route(web::put().to(change_password)))
+            .service(web::resource("/password/forgot/").route(web::post().to(forgot_password)))
+            .service(
+                web::resource("/password/reset/")
+                    .route(web::get().to(reset_password_page))
+                    .route(web::post().to(reset_password)),
+            )
             .service(web::resource("/login/").route(web::post().to(login)))
             .service(web::resource("/logout/").route(web::post().to(logout)))
+            .service(web::resource("/sessions/").route(web::get().to(get_sessions)))
+            .service(
+                web::resource("/sessions/{session_id}/").route(web::delete().to(delete_session)),
+            )
             .service(web::resource("/wines/").route(web::get().to(get_wines)))
+            .service(web::resource("/catalogue/").route(web::get().to(get_catalogue)))
+            .service(web::resource("/search/").route(web::get().to(search_wines)))
+            .service(web::resource("/wines/{saq_wine_id}/").route(web::get().to(get_wine)))
+            .service(
+                web::resource("/wines/{saq_wine_id}/prices/").route(web::get().to(get_wine_prices)),
+            )
             .service(
                 web::resource("/winerecommendations/")
                     .route(web::post().to(create_wine_reco))
@@ -66,6 +164,7 @@ async fn main() -> std::io::Result<()> {
             )
             .service(
                 web::resource("/winerecommendations/{wine_recommendation_id}/")
+                    .route(web::get().to(get_one_wine_reco))
                     .route(web::put().to(update_wine_reco))
                     .route(web::delete().to(delete_wine_reco)),
             )
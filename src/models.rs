@@ -1,14 +1,27 @@
-use crate::schema::{saq_wines, users, wine_recommendations};
-use crate::types::WineColorEnum;
-use argon2rs::{argon2i_simple, defaults, Argon2, Variant};
+use crate::schema::{
+    api_tokens, crawl_failures, crawl_runs, notifications, reco_matches, saq_wine_prices,
+    saq_wines, sessions, user_tokens, users, wine_alerts, wine_recommendations,
+};
+use crate::types::{AlertKindEnum, ApiScopeEnum, CrawlStatusEnum, TokenPurposeEnum, WineColorEnum};
+use crate::utils::env_or;
 use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::pg::expression::dsl::any;
 use diesel::prelude::PgConnection;
 use diesel::query_dsl::RunQueryDsl;
-use std::env;
+use diesel::result::DatabaseErrorKind;
+use diesel::result::Error::DatabaseError;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, JoinOnDsl, NullableExpressionMethods,
+    OptionalExtension, PgTextExpressionMethods, QueryDsl, QueryResult, TextExpressionMethods,
+};
+use openssl::sha::sha256;
+use rand::Rng;
 use std::error::Error;
 
-#[derive(Queryable)]
+#[derive(Identifiable, Queryable, Serialize)]
 pub struct SaqWine {
     pub id: i32,
     pub name: String,
@@ -24,11 +37,18 @@ pub struct SaqWine {
     pub color: WineColorEnum,
     pub grape_varieties: Vec<String>,
     pub available_online: bool,
+    pub saq_code: Option<String>,
+    // wines not seen by the last successful crawl
+    pub delisted: bool,
+    pub last_seen_at: NaiveDateTime,
+    // product page on saq.com
+    pub url: Option<String>,
 }
 
-#[derive(Insertable)]
+#[derive(Insertable, AsChangeset, PartialEq, Debug)]
 #[table_name = "saq_wines"]
 pub struct NewSaqWine<'a> {
+    pub saq_code: &'a str,
     pub name: &'a str,
     pub country: &'a str,
     pub region: &'a str,
@@ -41,42 +61,210 @@ pub struct NewSaqWine<'a> {
     pub color: &'a WineColorEnum,
     pub grape_varieties: &'a Vec<String>,
     pub available_online: &'a bool,
+    pub url: &'a str,
 }
 
-pub fn create_saq_wine<'a>(
+pub fn upsert_saq_wine<'a>(
     conn: &PgConnection,
-    name: &'a str,
-    country: &'a str,
-    region: &'a str,
-    designation_of_origin: &'a str,
-    regulated_designation: &'a bool,
-    producer: &'a str,
-    volume: &'a BigDecimal,
+    new_saq_wine: &'a NewSaqWine<'a>,
+) -> QueryResult<(SaqWine, Option<SaqWine>)> {
+    let previous_saq_wine = saq_wines::table
+        .filter(saq_wines::saq_code.eq(new_saq_wine.saq_code))
+        .first::<SaqWine>(conn)
+        .optional()?;
+
+    // the SAQ code is stable across crawls, refresh the existing row if we already know it.
+    let saq_wine = diesel::insert_into(saq_wines::table)
+        .values(new_saq_wine)
+        .on_conflict(saq_wines::saq_code)
+        .do_update()
+        .set((
+            new_saq_wine,
+            saq_wines::delisted.eq(false),
+            saq_wines::last_seen_at.eq(now),
+        ))
+        .get_result(conn)?;
+    Ok((saq_wine, previous_saq_wine))
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(SaqWine)]
+pub struct SaqWinePrice {
+    pub id: i32,
+    pub saq_wine_id: i32,
+    pub price: BigDecimal,
+    pub recorded_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "saq_wine_prices"]
+pub struct NewSaqWinePrice<'a> {
+    pub saq_wine_id: i32,
+    pub price: &'a BigDecimal,
+}
+
+pub fn create_saq_wine_price<'a>(
+    conn: &PgConnection,
+    saq_wine_id: i32,
     price: &'a BigDecimal,
-    alcohol_percent: &'a BigDecimal,
-    color: &'a WineColorEnum,
-    grape_varieties: &'a Vec<String>,
-    available_online: &'a bool,
-) -> SaqWine {
-    let new_saq_wine = NewSaqWine {
-        name: name,
-        country: country,
-        region: region,
-        designation_of_origin: designation_of_origin,
-        regulated_designation: regulated_designation,
-        producer: producer,
-        volume: volume,
+) -> QueryResult<SaqWinePrice> {
+    let new_saq_wine_price = NewSaqWinePrice {
+        saq_wine_id: saq_wine_id,
         price: price,
-        alcohol_percent: alcohol_percent,
-        color: color,
-        grape_varieties: grape_varieties,
-        available_online: available_online,
     };
+    diesel::insert_into(saq_wine_prices::table)
+        .values(&new_saq_wine_price)
+        .get_result(conn)
+}
 
-    diesel::insert_into(saq_wines::table)
-        .values(&new_saq_wine)
+pub fn delist_unseen_saq_wines(
+    conn: &PgConnection,
+    crawl_started_at: &NaiveDateTime,
+) -> QueryResult<usize> {
+    diesel::update(saq_wines::table.filter(saq_wines::last_seen_at.lt(crawl_started_at)))
+        .set(saq_wines::delisted.eq(true))
+        .execute(conn)
+}
+
+#[derive(Identifiable, Queryable, PartialEq, Debug, Serialize)]
+pub struct CrawlRun {
+    pub id: i32,
+    pub status: CrawlStatusEnum,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    pub pages_visited: i32,
+    pub wines_added: i32,
+    pub wines_updated: i32,
+    pub wines_failed: i32,
+    // fatal error that stopped the crawl
+    pub error: Option<String>,
+    pub cancel_requested: bool,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "crawl_runs"]
+pub struct CrawlRunProgress {
+    pub pages_visited: i32,
+    pub wines_added: i32,
+    pub wines_updated: i32,
+    pub wines_failed: i32,
+}
+
+// returns None when another crawl is already running.
+pub fn create_crawl_run(conn: &PgConnection) -> QueryResult<Option<CrawlRun>> {
+    let result = diesel::insert_into(crawl_runs::table)
+        .default_values()
+        .get_result(conn);
+    match result {
+        Ok(crawl_run) => Ok(Some(crawl_run)),
+        Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn get_running_crawl_run(conn: &PgConnection) -> QueryResult<Option<CrawlRun>> {
+    crawl_runs::table
+        .filter(crawl_runs::status.eq(CrawlStatusEnum::Running))
+        .first(conn)
+        .optional()
+}
+
+pub fn request_crawl_run_cancel(conn: &PgConnection, crawl_run_id: i32) -> QueryResult<CrawlRun> {
+    diesel::update(crawl_runs::table.find(crawl_run_id))
+        .set(crawl_runs::cancel_requested.eq(true))
+        .get_result(conn)
+}
+
+pub fn is_crawl_run_cancelled(conn: &PgConnection, crawl_run_id: i32) -> QueryResult<bool> {
+    crawl_runs::table
+        .find(crawl_run_id)
+        .select(crawl_runs::cancel_requested)
+        .first(conn)
+}
+
+// crawls still marked as running when the server starts were killed with it.
+pub fn fail_interrupted_crawl_runs(conn: &PgConnection) -> QueryResult<usize> {
+    diesel::update(crawl_runs::table.filter(crawl_runs::status.eq(CrawlStatusEnum::Running)))
+        .set((
+            crawl_runs::status.eq(CrawlStatusEnum::Failed),
+            crawl_runs::finished_at.eq(now.nullable()),
+            crawl_runs::error.eq("Interrupted by a server restart."),
+        ))
+        .execute(conn)
+}
+
+pub fn update_crawl_run_progress(
+    conn: &PgConnection,
+    crawl_run_id: i32,
+    progress: &CrawlRunProgress,
+) -> QueryResult<CrawlRun> {
+    diesel::update(crawl_runs::table.find(crawl_run_id))
+        .set(progress)
+        .get_result(conn)
+}
+
+pub fn finish_crawl_run(
+    conn: &PgConnection,
+    crawl_run_id: i32,
+    status: CrawlStatusEnum,
+    error: Option<String>,
+) -> QueryResult<CrawlRun> {
+    diesel::update(crawl_runs::table.find(crawl_run_id))
+        .set((
+            crawl_runs::status.eq(status),
+            crawl_runs::finished_at.eq(now.nullable()),
+            crawl_runs::error.eq(error),
+        ))
+        .get_result(conn)
+}
+
+// for crawls that stopped before they could finish themselves, finished runs are left alone.
+pub fn fail_crawl_run(conn: &PgConnection, crawl_run_id: i32, error: &str) -> QueryResult<usize> {
+    diesel::update(
+        crawl_runs::table
+            .find(crawl_run_id)
+            .filter(crawl_runs::status.eq(CrawlStatusEnum::Running)),
+    )
+    .set((
+        crawl_runs::status.eq(CrawlStatusEnum::Failed),
+        crawl_runs::finished_at.eq(now.nullable()),
+        crawl_runs::error.eq(error),
+    ))
+    .execute(conn)
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(CrawlRun)]
+pub struct CrawlFailure {
+    pub id: i32,
+    pub crawl_run_id: i32,
+    pub url: String,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "crawl_failures"]
+pub struct NewCrawlFailure<'a> {
+    pub crawl_run_id: i32,
+    pub url: &'a str,
+    pub message: &'a str,
+}
+
+pub fn create_crawl_failure<'a>(
+    conn: &PgConnection,
+    crawl_run_id: i32,
+    url: &'a str,
+    message: &'a str,
+) -> QueryResult<CrawlFailure> {
+    let new_crawl_failure = NewCrawlFailure {
+        crawl_run_id: crawl_run_id,
+        url: url,
+        message: message,
+    };
+    diesel::insert_into(crawl_failures::table)
+        .values(&new_crawl_failure)
         .get_result(conn)
-        .expect("Error saving new SAQ Wine.")
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize, Deserialize)]
@@ -94,7 +282,7 @@ pub struct WineRecommendation {
     pub name: String,
 }
 
-#[derive(Insertable, Serialize, Deserialize)]
+#[derive(Insertable)]
 #[table_name = "wine_recommendations"]
 pub struct NewWineRecommendation {
     pub country: String,
@@ -108,32 +296,184 @@ pub struct NewWineRecommendation {
     pub wine_name: String,
 }
 
+// recomputes the rows of reco_matches, for one recommendation or all of them.
+fn refresh_matches(conn: &PgConnection, wine_recommendation_id: Option<i32>) -> QueryResult<usize> {
+    use crate::schema::saq_wines as saq;
+    use crate::schema::wine_recommendations as recos;
+
+    let mut matches = saq::table
+        .inner_join(
+            recos::table.on(recos::country
+                .eq("")
+                .or(saq::country.ilike(recos::country))
+                .and(
+                    recos::region
+                        .eq("")
+                        .or(saq::region.ilike(recos::region))
+                        .and(
+                            recos::designation_of_origin
+                                .eq("")
+                                .or(saq::designation_of_origin
+                                    .ilike(recos::designation_of_origin.concat("%")))
+                                .and(
+                                    recos::wine_name
+                                        .eq("")
+                                        .or(saq::name.ilike(recos::wine_name.concat("%")))
+                                        .and(
+                                            recos::producer
+                                                .eq("")
+                                                .or(saq::producer.ilike(recos::producer))
+                                                .and(
+                                                    recos::grape_variety
+                                                        .eq("")
+                                                        .or(recos::grape_variety
+                                                            .ilike(any(saq::grape_varieties)))
+                                                        .and(saq::color.eq(recos::color)),
+                                                ),
+                                        ),
+                                ),
+                        ),
+                )),
+        )
+        .select((recos::id, saq::id))
+        .into_boxed();
+    if let Some(wine_recommendation_id) = wine_recommendation_id {
+        matches = matches.filter(recos::id.eq(wine_recommendation_id));
+    }
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        match wine_recommendation_id {
+            Some(wine_recommendation_id) => diesel::delete(
+                reco_matches::table
+                    .filter(reco_matches::wine_recommendation_id.eq(wine_recommendation_id)),
+            )
+            .execute(conn)?,
+            None => diesel::delete(reco_matches::table).execute(conn)?,
+        };
+        diesel::insert_into(reco_matches::table)
+            .values(matches)
+            .into_columns((
+                reco_matches::wine_recommendation_id,
+                reco_matches::saq_wine_id,
+            ))
+            .execute(conn)
+    })
+}
+
+// after a crawl, wines may have been added or changed.
+pub fn refresh_reco_matches(conn: &PgConnection) -> QueryResult<usize> {
+    refresh_matches(conn, None)
+}
+
+// after a recommendation was created or updated.
+pub fn refresh_wine_recommendation_matches(
+    conn: &PgConnection,
+    wine_recommendation_id: i32,
+) -> QueryResult<usize> {
+    refresh_matches(conn, Some(wine_recommendation_id))
+}
+
 pub fn create_wine_recommendation<'a>(
     conn: &PgConnection,
     new_wine_recommendation: &'a NewWineRecommendation,
-) -> WineRecommendation {
+) -> QueryResult<WineRecommendation> {
     diesel::insert_into(wine_recommendations::table)
         .values(new_wine_recommendation)
         .get_result(conn)
-        .expect("Error saving new wine recommendation.")
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(User)]
+pub struct WineAlert {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: AlertKindEnum,
+    // price of a 750 ml bottle
+    pub max_price: Option<BigDecimal>,
+    pub wine_recommendation_id: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize)]
+#[table_name = "wine_alerts"]
+pub struct NewWineAlert {
+    #[serde(skip)]
+    pub user_id: i32,
+    pub kind: AlertKindEnum,
+    pub max_price: Option<BigDecimal>,
+    pub wine_recommendation_id: Option<i32>,
+}
+
+pub fn create_wine_alert<'a>(
+    conn: &PgConnection,
+    new_wine_alert: &'a NewWineAlert,
+) -> QueryResult<WineAlert> {
+    diesel::insert_into(wine_alerts::table)
+        .values(new_wine_alert)
+        .get_result(conn)
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(User)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub wine_alert_id: Option<i32>,
+    pub saq_wine_id: i32,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub read_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "notifications"]
+pub struct NewNotification<'a> {
+    pub user_id: i32,
+    pub wine_alert_id: Option<i32>,
+    pub saq_wine_id: i32,
+    pub message: &'a str,
+}
+
+pub fn create_notification<'a>(
+    conn: &PgConnection,
+    new_notification: &'a NewNotification<'a>,
+) -> QueryResult<Notification> {
+    diesel::insert_into(notifications::table)
+        .values(new_notification)
+        .get_result(conn)
+}
+
+pub fn mark_notification_delivered(
+    conn: &PgConnection,
+    notification_id: i32,
+) -> QueryResult<Notification> {
+    diesel::update(notifications::table.find(notification_id))
+        .set(notifications::delivered_at.eq(now.nullable()))
+        .get_result(conn)
 }
 
 pub fn parse_wine_color(string: &str) -> Result<WineColorEnum, Box<dyn Error>> {
     match string {
         "red" => Ok(WineColorEnum::Red),
         "white" => Ok(WineColorEnum::White),
-        "pink" => Ok(WineColorEnum::Pink),
+        "pink" | "rosé" | "rose" => Ok(WineColorEnum::Pink),
         _ => Err("Unrecognized enum variant".into()),
     }
 }
 
-#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Clone)]
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Clone, Serialize)]
 pub struct User {
     pub id: i32,
     pub email: String,
     pub admin: bool,
-    pub salt: Vec<u8>,
-    pub password: Vec<u8>,
+    // legacy argon2i hash, see passwords.rs
+    #[serde(skip)]
+    pub salt: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub password: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    pub email_verified_at: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -141,37 +481,301 @@ pub struct User {
 pub struct NewUser<'a> {
     pub email: &'a str,
     pub admin: &'a bool,
-    pub salt: &'a Vec<u8>,
-    pub password: &'a Vec<u8>,
-}
-
-pub fn hash_password(password: &String, salt: Vec<u8>) -> Vec<u8> {
-    let mut out = [0; defaults::LENGTH];
-    let a2 = Argon2::default(Variant::Argon2i);
-    a2.hash(&mut out, password.as_bytes(), &salt, &[], &[]);
-    out.to_vec()
-}
-
-pub fn compute_salt(email: &String) -> Vec<u8> {
-    let secret_key = env::var("SECRET_KEY").expect("SECRET_KEY must be set");
-    argon2i_simple(email, &secret_key).to_vec()
+    pub password_hash: &'a str,
 }
 
 pub fn create_user<'a>(
     conn: &PgConnection,
     email: &'a str,
     admin: &'a bool,
-    salt: &'a Vec<u8>,
-    password: &'a Vec<u8>,
-) -> User {
+    password_hash: &'a str,
+) -> QueryResult<User> {
     let user = NewUser {
         email: email,
         admin: admin,
-        salt: salt,
-        password: password,
+        password_hash: password_hash,
     };
     diesel::insert_into(users::table)
         .values(&user)
         .get_result(conn)
-        .expect("Error saving new User.")
+}
+
+// the first admin can't be promoted through the API, the account must be verified so
+// nobody can register the address before its owner does.
+pub fn promote_admin(conn: &PgConnection, email: &str) -> QueryResult<usize> {
+    diesel::update(
+        users::table
+            .filter(users::email.eq(email))
+            .filter(users::email_verified_at.is_not_null())
+            .filter(users::admin.eq(false)),
+    )
+    .set(users::admin.eq(true))
+    .execute(conn)
+}
+
+// also drops the legacy hash, if any.
+pub fn update_password_hash(
+    conn: &PgConnection,
+    user_id: i32,
+    password_hash: &str,
+) -> QueryResult<User> {
+    diesel::update(users::table.find(user_id))
+        .set((
+            users::password_hash.eq(password_hash),
+            users::salt.eq(None::<Vec<u8>>),
+            users::password.eq(None::<Vec<u8>>),
+        ))
+        .get_result(conn)
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(User)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip)]
+    pub token_hash: Vec<u8>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "sessions"]
+pub struct NewSession<'a> {
+    pub user_id: i32,
+    pub token_hash: &'a Vec<u8>,
+    pub user_agent: Option<&'a str>,
+    pub expires_at: NaiveDateTime,
+}
+
+pub fn session_lifetime_days() -> i64 {
+    env_or("SESSION_LIFETIME_DAYS", 30)
+}
+
+// random hex token for cookies and emails, only its hash is stored.
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn hash_token(token: &str) -> Vec<u8> {
+    sha256(token.as_bytes()).to_vec()
+}
+
+// returns the session along with its token, which only the cookie keeps.
+pub fn create_session(
+    conn: &PgConnection,
+    user_id: i32,
+    user_agent: Option<&str>,
+) -> QueryResult<(Session, String)> {
+    let token = generate_token();
+    let new_session = NewSession {
+        user_id: user_id,
+        token_hash: &hash_token(&token),
+        user_agent: user_agent,
+        expires_at: Utc::now().naive_utc() + Duration::days(session_lifetime_days()),
+    };
+    // the user's expired sessions are of no use anymore
+    diesel::delete(
+        sessions::table
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::expires_at.le(now)),
+    )
+    .execute(conn)?;
+    let session = diesel::insert_into(sessions::table)
+        .values(&new_session)
+        .get_result(conn)?;
+    Ok((session, token))
+}
+
+// None when the session was revoked or has expired.
+pub fn find_session(conn: &PgConnection, token: &str) -> QueryResult<Option<(Session, User)>> {
+    let session = sessions::table
+        .inner_join(users::table)
+        .filter(sessions::token_hash.eq(hash_token(token)))
+        .filter(sessions::expires_at.gt(now))
+        .first::<(Session, User)>(conn)
+        .optional()?;
+    if let Some((session, _)) = &session {
+        // a write per request is not worth a more accurate last_seen_at
+        diesel::update(
+            sessions::table
+                .find(session.id)
+                .filter(sessions::last_seen_at.lt(now - 1.minute())),
+        )
+        .set(sessions::last_seen_at.eq(now))
+        .execute(conn)?;
+    }
+    Ok(session)
+}
+
+pub fn revoke_session(conn: &PgConnection, token: &str) -> QueryResult<usize> {
+    diesel::delete(sessions::table.filter(sessions::token_hash.eq(hash_token(token)))).execute(conn)
+}
+
+// logs the user out everywhere.
+pub fn revoke_user_sessions(conn: &PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id))).execute(conn)
+}
+
+// logs the user out everywhere but the given session.
+pub fn revoke_other_sessions(conn: &PgConnection, session: &Session) -> QueryResult<usize> {
+    diesel::delete(
+        sessions::table
+            .filter(sessions::user_id.eq(session.user_id))
+            .filter(sessions::id.ne(session.id)),
+    )
+    .execute(conn)
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug)]
+#[belongs_to(User)]
+pub struct UserToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub purpose: TokenPurposeEnum,
+    pub token_hash: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "user_tokens"]
+pub struct NewUserToken<'a> {
+    pub user_id: i32,
+    pub purpose: TokenPurposeEnum,
+    pub token_hash: &'a Vec<u8>,
+    pub expires_at: NaiveDateTime,
+}
+
+// returns the token to email, a new one replaces the unused ones of the same purpose.
+pub fn create_user_token(
+    conn: &PgConnection,
+    user_id: i32,
+    purpose: TokenPurposeEnum,
+    lifetime: Duration,
+) -> QueryResult<String> {
+    let token = generate_token();
+    let new_user_token = NewUserToken {
+        user_id: user_id,
+        purpose: purpose,
+        token_hash: &hash_token(&token),
+        expires_at: Utc::now().naive_utc() + lifetime,
+    };
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        discard_user_tokens(conn, user_id, purpose)?;
+        diesel::insert_into(user_tokens::table)
+            .values(&new_user_token)
+            .execute(conn)
+    })?;
+    Ok(token)
+}
+
+// unused tokens of the purpose stop working.
+pub fn discard_user_tokens(
+    conn: &PgConnection,
+    user_id: i32,
+    purpose: TokenPurposeEnum,
+) -> QueryResult<usize> {
+    diesel::delete(
+        user_tokens::table
+            .filter(user_tokens::user_id.eq(user_id))
+            .filter(user_tokens::purpose.eq(purpose))
+            .filter(user_tokens::used_at.is_null()),
+    )
+    .execute(conn)
+}
+
+// tokens are single use, None when unknown, expired or already used.
+pub fn use_user_token(
+    conn: &PgConnection,
+    token: &str,
+    purpose: TokenPurposeEnum,
+) -> QueryResult<Option<UserToken>> {
+    diesel::update(
+        user_tokens::table
+            .filter(user_tokens::token_hash.eq(hash_token(token)))
+            .filter(user_tokens::purpose.eq(purpose))
+            .filter(user_tokens::used_at.is_null())
+            .filter(user_tokens::expires_at.gt(now)),
+    )
+    .set(user_tokens::used_at.eq(now.nullable()))
+    .get_result(conn)
+    .optional()
+}
+
+pub fn verify_user_email(conn: &PgConnection, user_id: i32) -> QueryResult<User> {
+    diesel::update(users::table.find(user_id))
+        .set(users::email_verified_at.eq(now.nullable()))
+        .get_result(conn)
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(User)]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub scopes: Vec<ApiScopeEnum>,
+    #[serde(skip)]
+    pub token_hash: Vec<u8>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[table_name = "api_tokens"]
+pub struct NewApiToken<'a> {
+    pub user_id: i32,
+    pub name: &'a str,
+    pub scopes: &'a Vec<ApiScopeEnum>,
+    pub token_hash: &'a Vec<u8>,
+}
+
+// returns the token along with its plain value, which is only shown once.
+pub fn create_api_token(
+    conn: &PgConnection,
+    user_id: i32,
+    name: &str,
+    scopes: &Vec<ApiScopeEnum>,
+) -> QueryResult<(ApiToken, String)> {
+    let token = generate_token();
+    let new_api_token = NewApiToken {
+        user_id: user_id,
+        name: name,
+        scopes: scopes,
+        token_hash: &hash_token(&token),
+    };
+    let api_token = diesel::insert_into(api_tokens::table)
+        .values(&new_api_token)
+        .get_result(conn)?;
+    Ok((api_token, token))
+}
+
+// None when the token was revoked, along with the user who created it.
+pub fn find_api_token(conn: &PgConnection, token: &str) -> QueryResult<Option<(ApiToken, User)>> {
+    let api_token = api_tokens::table
+        .inner_join(users::table)
+        .filter(api_tokens::token_hash.eq(hash_token(token)))
+        .first::<(ApiToken, User)>(conn)
+        .optional()?;
+    if let Some((api_token, _)) = &api_token {
+        // same as sessions, last_used_at is only accurate to the minute
+        diesel::update(
+            api_tokens::table.find(api_token.id).filter(
+                api_tokens::last_used_at
+                    .is_null()
+                    .or(api_tokens::last_used_at.lt((now - 1.minute()).nullable())),
+            ),
+        )
+        .set(api_tokens::last_used_at.eq(now.nullable()))
+        .execute(conn)?;
+    }
+    Ok(api_token)
 }
@@ -0,0 +1,108 @@
+use crate::errors::NotifyError;
+use crate::mailer::{Email, Mailer, SmtpMailer};
+use crate::models::{mark_notification_delivered, Notification, User};
+use crate::schema::{notifications, users};
+use diesel::pg::PgConnection;
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use reqwest;
+use std::env;
+
+pub trait Notifier {
+    fn notify(&self, user: &User, notification: &Notification) -> Result<(), NotifyError>;
+}
+
+// notifications are already readable from GET /notifications/, nothing to send.
+pub struct InboxNotifier;
+
+impl Notifier for InboxNotifier {
+    fn notify(&self, _user: &User, _notification: &Notification) -> Result<(), NotifyError> {
+        Ok(())
+    }
+}
+
+pub struct WebhookNotifier {
+    pub url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> WebhookNotifier {
+        WebhookNotifier {
+            url: url,
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, user: &User, notification: &Notification) -> Result<(), NotifyError> {
+        self.client
+            .post(&self.url)
+            .json(&json!({
+                "email": user.email,
+                "notification": notification,
+            }))
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|error| NotifyError {
+                email: user.email.clone(),
+                message: error.to_string(),
+            })
+    }
+}
+
+pub struct SmtpNotifier {
+    pub mailer: SmtpMailer,
+}
+
+impl Notifier for SmtpNotifier {
+    fn notify(&self, user: &User, notification: &Notification) -> Result<(), NotifyError> {
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Wine alert".to_string(),
+            body: notification.message.clone(),
+        };
+        self.mailer.send(&email).map_err(NotifyError::from)
+    }
+}
+
+// NOTIFIER is one of inbox (default), webhook or smtp.
+pub fn notifier_from_env() -> Box<dyn Notifier> {
+    match env::var("NOTIFIER")
+        .unwrap_or_else(|_| "inbox".to_string())
+        .as_str()
+    {
+        "webhook" => Box::new(WebhookNotifier::new(
+            env::var("NOTIFIER_WEBHOOK_URL").expect("NOTIFIER_WEBHOOK_URL must be set"),
+        )),
+        "smtp" => Box::new(SmtpNotifier {
+            mailer: SmtpMailer::from_env(),
+        }),
+        "inbox" => Box::new(InboxNotifier),
+        notifier => panic!("Unknown NOTIFIER {}", notifier),
+    }
+}
+
+// failed deliveries stay pending and are retried after the next crawl.
+pub fn deliver_pending_notifications(
+    conn: &PgConnection,
+    notifier: &dyn Notifier,
+) -> QueryResult<usize> {
+    let pending = notifications::table
+        .inner_join(users::table)
+        .filter(notifications::delivered_at.is_null())
+        .order(notifications::id)
+        .load::<(Notification, User)>(conn)?;
+    let mut delivered = 0;
+    for (notification, user) in pending {
+        match notifier.notify(&user, &notification) {
+            Ok(()) => {
+                mark_notification_delivered(conn, notification.id)?;
+                delivered += 1;
+            }
+            Err(error) => println!("{}", error),
+        }
+    }
+    Ok(delivered)
+}
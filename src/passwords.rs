@@ -0,0 +1,146 @@
+use crate::models::User;
+use crate::utils::env_or;
+use argon2::{Config, ThreadMode, Variant, Version};
+use argon2rs::{defaults, Argon2};
+use openssl::memcmp;
+use rand::Rng;
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordCheck {
+    Invalid,
+    Valid,
+    // valid, but hashed with a legacy scheme or outdated parameters
+    ValidNeedsRehash,
+}
+
+// argon2id, OWASP's minimum parameters by default.
+fn argon2_config() -> Config<'static> {
+    Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: env_or("ARGON2_MEMORY_KIB", 19456),
+        time_cost: env_or("ARGON2_ITERATIONS", 2),
+        lanes: env_or("ARGON2_PARALLELISM", 1),
+        thread_mode: ThreadMode::Sequential,
+        secret: &[],
+        ad: &[],
+        hash_length: 32,
+    }
+}
+
+// PHC string embedding a random salt and the parameters it was hashed with.
+pub fn hash_password(password: &str) -> String {
+    let salt = rand::thread_rng().gen::<[u8; 16]>();
+    argon2::hash_encoded(password.as_bytes(), &salt, &argon2_config())
+        .expect("Error hashing password")
+}
+
+pub fn verify_password(user: &User, password: &str) -> PasswordCheck {
+    match (&user.password_hash, &user.salt, &user.password) {
+        (Some(password_hash), _, _) => {
+            // verify_encoded compares in constant time
+            if !argon2::verify_encoded(password_hash, password.as_bytes()).unwrap_or(false) {
+                return PasswordCheck::Invalid;
+            }
+            if password_hash.starts_with(&phc_prefix(&argon2_config())) {
+                PasswordCheck::Valid
+            } else {
+                PasswordCheck::ValidNeedsRehash
+            }
+        }
+        (None, Some(salt), Some(legacy_password)) => {
+            let legacy_hash = legacy_hash_password(password, salt);
+            if legacy_hash.len() == legacy_password.len()
+                && memcmp::eq(&legacy_hash, legacy_password)
+            {
+                PasswordCheck::ValidNeedsRehash
+            } else {
+                PasswordCheck::Invalid
+            }
+        }
+        _ => PasswordCheck::Invalid,
+    }
+}
+
+// hashes for nothing, so unknown emails take as long as wrong passwords.
+pub fn dummy_password_check(password: &str) {
+    hash_password(password);
+}
+
+fn phc_prefix(config: &Config) -> String {
+    format!(
+        "${}$v={}$m={},t={},p={}$",
+        config.variant.as_lowercase_str(),
+        config.version.as_u32(),
+        config.mem_cost,
+        config.time_cost,
+        config.lanes
+    )
+}
+
+// argon2i with a salt derived from the email and SECRET_KEY, kept until users log in again.
+fn legacy_hash_password(password: &str, salt: &[u8]) -> Vec<u8> {
+    let mut out = [0; defaults::LENGTH];
+    let a2 = Argon2::default(argon2rs::Variant::Argon2i);
+    a2.hash(&mut out, password.as_bytes(), salt, &[], &[]);
+    out.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User {
+            id: 1,
+            email: "jp@example.com".to_string(),
+            admin: false,
+            salt: None,
+            password: None,
+            password_hash: None,
+            email_verified_at: None,
+        }
+    }
+
+    #[test]
+    fn test_hash_password() {
+        let password_hash = hash_password("hunter2");
+        assert!(password_hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+        // the salt is random
+        assert_ne!(password_hash, hash_password("hunter2"));
+
+        let mut user = user();
+        user.password_hash = Some(password_hash);
+        assert_eq!(verify_password(&user, "hunter2"), PasswordCheck::Valid);
+        assert_eq!(verify_password(&user, "hunter3"), PasswordCheck::Invalid);
+    }
+
+    #[test]
+    fn test_verify_outdated_password_hash() {
+        let config = Config {
+            mem_cost: 4096,
+            ..argon2_config()
+        };
+        let mut user = user();
+        user.password_hash =
+            Some(argon2::hash_encoded(b"hunter2", b"somesaltsomesalt", &config).unwrap());
+        assert_eq!(
+            verify_password(&user, "hunter2"),
+            PasswordCheck::ValidNeedsRehash
+        );
+        assert_eq!(verify_password(&user, "hunter3"), PasswordCheck::Invalid);
+    }
+
+    #[test]
+    fn test_verify_legacy_password() {
+        let salt = b"legacysaltlegacysalt".to_vec();
+        let mut user = user();
+        user.password = Some(legacy_hash_password("hunter2", &salt));
+        user.salt = Some(salt);
+        assert_eq!(
+            verify_password(&user, "hunter2"),
+            PasswordCheck::ValidNeedsRehash
+        );
+        assert_eq!(verify_password(&user, "hunter3"), PasswordCheck::Invalid);
+    }
+}
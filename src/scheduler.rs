@@ -0,0 +1,160 @@
+use crate::crawler::start_crawl;
+use crate::db::DbPool;
+use chrono::{NaiveDateTime, NaiveTime, Utc};
+use std::thread;
+use std::time::Duration;
+
+// longer intervals are better served by a daily schedule anyway.
+const MAX_INTERVAL_SECONDS: u64 = 30 * 24 * 3600;
+
+#[derive(Debug, PartialEq)]
+pub enum CrawlSchedule {
+    // every day at the given time (UTC)
+    Daily(NaiveTime),
+    Every(Duration),
+}
+
+impl CrawlSchedule {
+    // e.g. "daily 03:00", "every 6h" or "every 30m"
+    pub fn parse(schedule: &str) -> Option<CrawlSchedule> {
+        let mut parts = schedule.split_whitespace();
+        let kind = parts.next()?;
+        let value = parts.next()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        match kind {
+            "daily" => NaiveTime::parse_from_str(value, "%H:%M")
+                .ok()
+                .map(CrawlSchedule::Daily),
+            "every" => {
+                let unit_index = value.find(|c: char| !c.is_ascii_digit())?;
+                let amount = value[..unit_index].parse::<u64>().ok()?;
+                let seconds = match &value[unit_index..] {
+                    "h" => amount.checked_mul(3600)?,
+                    "m" => amount.checked_mul(60)?,
+                    _ => return None,
+                };
+                if seconds == 0 || seconds > MAX_INTERVAL_SECONDS {
+                    return None;
+                }
+                Some(CrawlSchedule::Every(Duration::from_secs(seconds)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn next_run_after(&self, after: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            CrawlSchedule::Daily(at) => {
+                let next_run = after.date().and_time(*at);
+                if next_run > after {
+                    next_run
+                } else {
+                    next_run + chrono::Duration::days(1)
+                }
+            }
+            CrawlSchedule::Every(interval) => {
+                after + chrono::Duration::from_std(*interval).unwrap()
+            }
+        }
+    }
+}
+
+// crawls run in their own thread so we only need to wait for the next one here.
+// a crawl that fails to start is skipped, the scheduler keeps going.
+pub fn spawn_crawl_scheduler(schedule: CrawlSchedule, pool: DbPool) {
+    thread::spawn(move || loop {
+        let current_time = Utc::now().naive_utc();
+        let next_run = schedule.next_run_after(current_time);
+        println!("Next scheduled crawl at {} UTC", next_run);
+        thread::sleep((next_run - current_time).to_std().unwrap_or_default());
+        let started = pool
+            .get()
+            .map_err(|error| error.to_string())
+            .and_then(|conn| start_crawl(&conn).map_err(|error| error.to_string()));
+        match started {
+            Ok(Some(crawl_run)) => println!("Scheduled crawl {} has been started", crawl_run.id),
+            Ok(None) => println!("Scheduled crawl skipped, a crawl is already running"),
+            Err(error) => println!("Scheduled crawl failed to start: {}", error),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2026, 10, 18).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            CrawlSchedule::parse("daily 03:00"),
+            Some(CrawlSchedule::Daily(NaiveTime::from_hms(3, 0, 0)))
+        );
+        assert_eq!(
+            CrawlSchedule::parse("every 6h"),
+            Some(CrawlSchedule::Every(Duration::from_secs(6 * 3600)))
+        );
+        assert_eq!(
+            CrawlSchedule::parse("every 30m"),
+            Some(CrawlSchedule::Every(Duration::from_secs(30 * 60)))
+        );
+        assert_eq!(
+            CrawlSchedule::parse("every 720h"),
+            Some(CrawlSchedule::Every(Duration::from_secs(720 * 3600)))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for schedule in &[
+            "",
+            "daily",
+            "daily 25:00",
+            "daily 03:00 UTC",
+            "weekly 03:00",
+            "every",
+            "every 6",
+            "every h",
+            "every 0h",
+            "every 6d",
+            "every -6h",
+            "every 721h",
+            "every 9999999999999h",
+            "every 99999999999999999999m",
+        ] {
+            assert_eq!(CrawlSchedule::parse(schedule), None, "{}", schedule);
+        }
+    }
+
+    #[test]
+    fn test_next_run_after_daily() {
+        let schedule = CrawlSchedule::Daily(NaiveTime::from_hms(3, 0, 0));
+        assert_eq!(schedule.next_run_after(at(1, 0)), at(3, 0));
+        // the time of day has passed, or is now, so tomorrow
+        assert_eq!(
+            schedule.next_run_after(at(3, 0)),
+            at(3, 0) + chrono::Duration::days(1)
+        );
+        assert_eq!(
+            schedule.next_run_after(at(23, 59)),
+            at(3, 0) + chrono::Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn test_next_run_after_every() {
+        let schedule = CrawlSchedule::parse("every 6h").unwrap();
+        assert_eq!(schedule.next_run_after(at(1, 0)), at(7, 0));
+        let schedule = CrawlSchedule::parse("every 90m").unwrap();
+        assert_eq!(
+            schedule.next_run_after(at(23, 0)),
+            at(0, 30) + chrono::Duration::days(1)
+        );
+    }
+}
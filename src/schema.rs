@@ -1,3 +1,73 @@
+table! {
+    use crate::types::Api_scope;
+    use diesel::sql_types::*;
+    api_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        scopes -> Array<Api_scope>,
+        token_hash -> Bytea,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    crawl_failures (id) {
+        id -> Int4,
+        crawl_run_id -> Int4,
+        url -> Varchar,
+        message -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use crate::types::Crawl_status;
+    use diesel::sql_types::*;
+    crawl_runs (id) {
+        id -> Int4,
+        status -> Crawl_status,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        pages_visited -> Int4,
+        wines_added -> Int4,
+        wines_updated -> Int4,
+        wines_failed -> Int4,
+        error -> Nullable<Varchar>,
+        cancel_requested -> Bool,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Int4,
+        user_id -> Int4,
+        wine_alert_id -> Nullable<Int4>,
+        saq_wine_id -> Int4,
+        message -> Varchar,
+        created_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        read_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reco_matches (wine_recommendation_id, saq_wine_id) {
+        wine_recommendation_id -> Int4,
+        saq_wine_id -> Int4,
+    }
+}
+
+table! {
+    saq_wine_prices (id) {
+        id -> Int4,
+        saq_wine_id -> Int4,
+        price -> Numeric,
+        recorded_at -> Timestamp,
+    }
+}
+
 table! {
     use crate::types::Wine_color;
     use diesel::sql_types::*;
@@ -15,6 +85,36 @@ table! {
         color -> Wine_color,
         grape_varieties -> Array<Text>,
         available_online -> Bool,
+        saq_code -> Nullable<Varchar>,
+        delisted -> Bool,
+        last_seen_at -> Timestamp,
+        url -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Bytea,
+        user_agent -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    use crate::types::Token_purpose;
+    use diesel::sql_types::*;
+    user_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        purpose -> Token_purpose,
+        token_hash -> Bytea,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
     }
 }
 
@@ -23,8 +123,23 @@ table! {
         id -> Int4,
         email -> Varchar,
         admin -> Bool,
-        salt -> Bytea,
-        password -> Bytea,
+        salt -> Nullable<Bytea>,
+        password -> Nullable<Bytea>,
+        password_hash -> Nullable<Varchar>,
+        email_verified_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use crate::types::Alert_kind;
+    use diesel::sql_types::*;
+    wine_alerts (id) {
+        id -> Int4,
+        user_id -> Int4,
+        kind -> Alert_kind,
+        max_price -> Nullable<Numeric>,
+        wine_recommendation_id -> Nullable<Int4>,
+        created_at -> Timestamp,
     }
 }
 
@@ -45,6 +160,31 @@ table! {
     }
 }
 
+joinable!(api_tokens -> users (user_id));
+joinable!(crawl_failures -> crawl_runs (crawl_run_id));
+joinable!(notifications -> saq_wines (saq_wine_id));
+joinable!(notifications -> users (user_id));
+joinable!(notifications -> wine_alerts (wine_alert_id));
+joinable!(reco_matches -> saq_wines (saq_wine_id));
+joinable!(reco_matches -> wine_recommendations (wine_recommendation_id));
+joinable!(saq_wine_prices -> saq_wines (saq_wine_id));
+joinable!(sessions -> users (user_id));
+joinable!(user_tokens -> users (user_id));
+joinable!(wine_alerts -> users (user_id));
+joinable!(wine_alerts -> wine_recommendations (wine_recommendation_id));
 joinable!(wine_recommendations -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(saq_wines, users, wine_recommendations,);
+allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    crawl_failures,
+    crawl_runs,
+    notifications,
+    reco_matches,
+    saq_wine_prices,
+    saq_wines,
+    sessions,
+    user_tokens,
+    users,
+    wine_alerts,
+    wine_recommendations,
+);
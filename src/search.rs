@@ -0,0 +1,47 @@
+use crate::schema::saq_wines as saq;
+use crate::types::{Tsquery, Tsvector};
+use diesel::expression::BoxableExpression;
+use diesel::pg::Pg;
+use diesel::sql_types::{Array, Bool, Float4, Text};
+use diesel::BoolExpressionMethods;
+
+// the SQL functions are created by the saq_wines_search migration, the same expressions
+// are indexed so they must not change without a migration.
+sql_function!(fn saq_wine_search_vector(name: Text, producer: Text, region: Text, grape_varieties: Array<Text>) -> Tsvector);
+sql_function!(fn saq_wine_search_query(query: Text) -> Tsquery);
+sql_function!(fn saq_wine_search_text(name: Text, producer: Text) -> Text);
+sql_function!(fn normalize_search_text(text: Text) -> Text);
+sql_function!(fn ts_rank(vector: Tsvector, query: Tsquery) -> Float4);
+sql_function!(fn word_similarity(left: Text, right: Text) -> Float4);
+
+diesel_infix_operator!(TsMatches, " @@ ", backend: Pg);
+diesel_infix_operator!(WordSimilar, " <% ", backend: Pg);
+
+fn search_vector(
+) -> saq_wine_search_vector::HelperType<saq::name, saq::producer, saq::region, saq::grape_varieties>
+{
+    saq_wine_search_vector(saq::name, saq::producer, saq::region, saq::grape_varieties)
+}
+
+// full text match on name, producer, region and grapes, or close enough to the name
+// and producer to be a typo.
+pub fn saq_wine_search(query: &str) -> Box<dyn BoxableExpression<saq::table, Pg, SqlType = Bool>> {
+    let full_text_match = TsMatches::new(search_vector(), saq_wine_search_query(query.to_string()));
+    let fuzzy_match = WordSimilar::new(
+        normalize_search_text(query.to_string()),
+        saq_wine_search_text(saq::name, saq::producer),
+    );
+    Box::new(full_text_match.or(fuzzy_match))
+}
+
+pub fn saq_wine_search_rank(
+    query: &str,
+) -> Box<dyn BoxableExpression<saq::table, Pg, SqlType = Float4>> {
+    Box::new(
+        ts_rank(search_vector(), saq_wine_search_query(query.to_string()))
+            + word_similarity(
+                normalize_search_text(query.to_string()),
+                saq_wine_search_text(saq::name, saq::producer),
+            ),
+    )
+}
@@ -8,7 +8,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::io::Write;
 
-#[derive(SqlType)]
+#[derive(SqlType, QueryId)]
 #[postgres(type_name = "wine_color")]
 #[allow(non_camel_case_types)]
 pub struct Wine_color;
@@ -86,3 +86,250 @@ impl FromSql<Wine_color, Pg> for WineColorEnum {
         }
     }
 }
+
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "crawl_status")]
+#[allow(non_camel_case_types)]
+pub struct Crawl_status;
+
+#[derive(Debug, PartialEq, FromSqlRow, AsExpression, Clone, Copy)]
+#[sql_type = "Crawl_status"]
+pub enum CrawlStatusEnum {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl Serialize for CrawlStatusEnum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            CrawlStatusEnum::Running => serializer.serialize_str("running"),
+            CrawlStatusEnum::Succeeded => serializer.serialize_str("succeeded"),
+            CrawlStatusEnum::Failed => serializer.serialize_str("failed"),
+            CrawlStatusEnum::Cancelled => serializer.serialize_str("cancelled"),
+        }
+    }
+}
+
+impl ToSql<Crawl_status, Pg> for CrawlStatusEnum {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            CrawlStatusEnum::Running => out.write_all(b"running")?,
+            CrawlStatusEnum::Succeeded => out.write_all(b"succeeded")?,
+            CrawlStatusEnum::Failed => out.write_all(b"failed")?,
+            CrawlStatusEnum::Cancelled => out.write_all(b"cancelled")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Crawl_status, Pg> for CrawlStatusEnum {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"running" => Ok(CrawlStatusEnum::Running),
+            b"succeeded" => Ok(CrawlStatusEnum::Succeeded),
+            b"failed" => Ok(CrawlStatusEnum::Failed),
+            b"cancelled" => Ok(CrawlStatusEnum::Cancelled),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "alert_kind")]
+#[allow(non_camel_case_types)]
+pub struct Alert_kind;
+
+#[derive(Debug, PartialEq, FromSqlRow, AsExpression, Clone, Copy)]
+#[sql_type = "Alert_kind"]
+pub enum AlertKindEnum {
+    PriceThreshold,
+    PriceDrop,
+    Restock,
+}
+
+struct AlertKindVisitor;
+
+impl<'de> Visitor<'de> for AlertKindVisitor {
+    type Value = AlertKindEnum;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a lowercase string price_threshold, price_drop or restock.")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match value.to_lowercase().as_ref() {
+            "price_threshold" => Ok(AlertKindEnum::PriceThreshold),
+            "price_drop" => Ok(AlertKindEnum::PriceDrop),
+            "restock" => Ok(AlertKindEnum::Restock),
+            _ => Err(de::Error::custom(format!("invalid alert kind: {}", value))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AlertKindEnum {
+    fn deserialize<D>(deserializer: D) -> Result<AlertKindEnum, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(AlertKindVisitor)
+    }
+}
+
+impl Serialize for AlertKindEnum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            AlertKindEnum::PriceThreshold => serializer.serialize_str("price_threshold"),
+            AlertKindEnum::PriceDrop => serializer.serialize_str("price_drop"),
+            AlertKindEnum::Restock => serializer.serialize_str("restock"),
+        }
+    }
+}
+
+impl ToSql<Alert_kind, Pg> for AlertKindEnum {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            AlertKindEnum::PriceThreshold => out.write_all(b"price_threshold")?,
+            AlertKindEnum::PriceDrop => out.write_all(b"price_drop")?,
+            AlertKindEnum::Restock => out.write_all(b"restock")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Alert_kind, Pg> for AlertKindEnum {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"price_threshold" => Ok(AlertKindEnum::PriceThreshold),
+            b"price_drop" => Ok(AlertKindEnum::PriceDrop),
+            b"restock" => Ok(AlertKindEnum::Restock),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+// full text search types, only used in queries and never loaded
+#[derive(SqlType)]
+#[postgres(type_name = "tsvector")]
+pub struct Tsvector;
+
+#[derive(SqlType)]
+#[postgres(type_name = "tsquery")]
+pub struct Tsquery;
+
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "token_purpose")]
+#[allow(non_camel_case_types)]
+pub struct Token_purpose;
+
+#[derive(Debug, PartialEq, FromSqlRow, AsExpression, Clone, Copy)]
+#[sql_type = "Token_purpose"]
+pub enum TokenPurposeEnum {
+    EmailVerification,
+    PasswordReset,
+}
+
+impl ToSql<Token_purpose, Pg> for TokenPurposeEnum {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            TokenPurposeEnum::EmailVerification => out.write_all(b"email_verification")?,
+            TokenPurposeEnum::PasswordReset => out.write_all(b"password_reset")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Token_purpose, Pg> for TokenPurposeEnum {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"email_verification" => Ok(TokenPurposeEnum::EmailVerification),
+            b"password_reset" => Ok(TokenPurposeEnum::PasswordReset),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "api_scope")]
+#[allow(non_camel_case_types)]
+pub struct Api_scope;
+
+// what an API token may be used for, sessions of admins may do everything.
+#[derive(Debug, PartialEq, FromSqlRow, AsExpression, Clone, Copy)]
+#[sql_type = "Api_scope"]
+pub enum ApiScopeEnum {
+    Crawl,
+    Users,
+}
+
+struct ApiScopeVisitor;
+
+impl<'de> Visitor<'de> for ApiScopeVisitor {
+    type Value = ApiScopeEnum;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a lowercase string crawl or users.")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match value.to_lowercase().as_ref() {
+            "crawl" => Ok(ApiScopeEnum::Crawl),
+            "users" => Ok(ApiScopeEnum::Users),
+            _ => Err(de::Error::custom(format!("invalid api scope: {}", value))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiScopeEnum {
+    fn deserialize<D>(deserializer: D) -> Result<ApiScopeEnum, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ApiScopeVisitor)
+    }
+}
+
+impl Serialize for ApiScopeEnum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            ApiScopeEnum::Crawl => serializer.serialize_str("crawl"),
+            ApiScopeEnum::Users => serializer.serialize_str("users"),
+        }
+    }
+}
+
+impl ToSql<Api_scope, Pg> for ApiScopeEnum {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            ApiScopeEnum::Crawl => out.write_all(b"crawl")?,
+            ApiScopeEnum::Users => out.write_all(b"users")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Api_scope, Pg> for ApiScopeEnum {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"crawl" => Ok(ApiScopeEnum::Crawl),
+            b"users" => Ok(ApiScopeEnum::Users),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
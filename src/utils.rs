@@ -1,39 +1,55 @@
-use crate::types::WineColorEnum;
-use bigdecimal::BigDecimal;
+use std::env;
+use std::str::FromStr;
 
-pub fn is_dup_wine(
-    wines: &Vec<(
-        i32,
-        String,
-        bool,
-        String,
-        String,
-        String,
-        String,
-        WineColorEnum,
-        BigDecimal,
-        BigDecimal,
-        i32,
-    )>,
-    wine: &(
-        i32,
-        String,
-        bool,
-        String,
-        String,
-        String,
-        String,
-        WineColorEnum,
-        BigDecimal,
-        BigDecimal,
-        i32,
-    ),
-    index: usize,
-) -> bool {
-    for (i, wine_tmp) in wines.iter().enumerate() {
-        if i != index && wine_tmp.0 == wine.0 && wine_tmp.10 >= wine.10 {
-            return true;
-        }
+// reads a setting from the environment, falling back on the default when unset or invalid.
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+// makes LIKE wildcards in user input match literally.
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// link to another page of a listing, keeping the other query parameters.
+pub fn next_page_link(path: &str, query_string: &str, page: i64, per_page: i64) -> String {
+    let mut query: Vec<&str> = query_string
+        .split('&')
+        .filter(|parameter| {
+            let key = parameter.split('=').next().unwrap_or("");
+            !parameter.is_empty() && key != "page" && key != "per_page"
+        })
+        .collect();
+    let pagination = format!("page={}&per_page={}", page, per_page);
+    query.push(&pagination);
+    format!("{}?{}", path, query.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("Côtes du Rhône"), "Côtes du Rhône");
+        assert_eq!(escape_like("100%_\\"), "100\\%\\_\\\\");
+    }
+
+    #[test]
+    fn test_next_page_link() {
+        assert_eq!(
+            next_page_link("/wines/", "", 2, 20),
+            "/wines/?page=2&per_page=20"
+        );
+        assert_eq!(
+            next_page_link("/wines/", "color=red&page=1&sort=price", 2, 50),
+            "/wines/?color=red&sort=price&page=2&per_page=50"
+        );
     }
-    return false;
 }
@@ -0,0 +1,184 @@
+use crate::errors::{ApiError, FieldError};
+use crate::utils::env_or;
+
+// recommendations are rated out of 100 unless RATING_SCALE=5.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RatingScale {
+    Hundred,
+    Five,
+}
+
+impl RatingScale {
+    pub fn from_env() -> RatingScale {
+        match env_or("RATING_SCALE", 100) {
+            5 => RatingScale::Five,
+            _ => RatingScale::Hundred,
+        }
+    }
+
+    pub fn min(self) -> i32 {
+        match self {
+            RatingScale::Hundred => 0,
+            RatingScale::Five => 1,
+        }
+    }
+
+    pub fn max(self) -> i32 {
+        match self {
+            RatingScale::Hundred => 100,
+            RatingScale::Five => 5,
+        }
+    }
+}
+
+const MAX_EMAIL_LENGTH: usize = 254;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+// emails are stored this way, so compare them this way too.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// collects every invalid field of a form so they are reported together.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        Validator::default()
+    }
+
+    pub fn add(&mut self, field: Option<&str>, message: &str) {
+        self.errors.push(FieldError {
+            field: field.map(str::to_string),
+            message: message.to_string(),
+        });
+    }
+
+    // returns the trimmed value, invalid or not.
+    pub fn text(&mut self, field: &str, value: &str, max_length: usize) -> String {
+        let value = value.trim();
+        if value.chars().count() > max_length {
+            self.add(
+                Some(field),
+                &format!("{} must be at most {} characters", field, max_length),
+            );
+        }
+        value.to_string()
+    }
+
+    // returns the normalized email, only checking it looks like one.
+    pub fn email(&mut self, field: &str, value: &str) -> String {
+        let value = self.text(field, &normalize_email(value), MAX_EMAIL_LENGTH);
+        if value.is_empty() {
+            self.add(Some(field), &format!("{} is required", field));
+        } else if !value.contains('@') {
+            self.add(Some(field), &format!("{} must be an email address", field));
+        }
+        value
+    }
+
+    // passwords are hashed as sent, never trimmed.
+    pub fn password(&mut self, field: &str, value: &str) {
+        let length = value.chars().count();
+        if length < MIN_PASSWORD_LENGTH || length > MAX_PASSWORD_LENGTH {
+            self.add(
+                Some(field),
+                &format!(
+                    "{} must be between {} and {} characters",
+                    field, MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+                ),
+            );
+        }
+    }
+
+    pub fn rating(&mut self, field: &str, value: i32, scale: RatingScale) {
+        if value < scale.min() || value > scale.max() {
+            self.add(
+                Some(field),
+                &format!(
+                    "{} must be between {} and {}",
+                    field,
+                    scale.min(),
+                    scale.max()
+                ),
+            );
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(ApiError::InvalidFields(self.errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let mut validator = Validator::new();
+        assert_eq!(validator.text("region", "  Bordeaux \n", 10), "Bordeaux");
+        assert_eq!(validator.text("producer", "Château", 7), "Château");
+        assert!(validator.finish().is_ok());
+
+        let mut validator = Validator::new();
+        validator.text("producer", "Château", 6);
+        match validator.finish() {
+            Err(ApiError::InvalidFields(errors)) => assert_eq!(
+                errors,
+                vec![FieldError {
+                    field: Some("producer".to_string()),
+                    message: "producer must be at most 6 characters".to_string(),
+                }]
+            ),
+            _ => panic!("producer should be too long"),
+        }
+    }
+
+    #[test]
+    fn test_rating() {
+        let mut validator = Validator::new();
+        validator.rating("rating", 0, RatingScale::Hundred);
+        validator.rating("rating", 100, RatingScale::Hundred);
+        validator.rating("rating", 1, RatingScale::Five);
+        validator.rating("rating", 5, RatingScale::Five);
+        assert!(validator.finish().is_ok());
+
+        let mut validator = Validator::new();
+        validator.rating("rating", 101, RatingScale::Hundred);
+        validator.rating("rating", -1, RatingScale::Hundred);
+        validator.rating("rating", 0, RatingScale::Five);
+        validator.rating("rating", 6, RatingScale::Five);
+        match validator.finish() {
+            Err(ApiError::InvalidFields(errors)) => assert_eq!(errors.len(), 4),
+            _ => panic!("ratings should be out of range"),
+        }
+    }
+
+    #[test]
+    fn test_email_and_password() {
+        let mut validator = Validator::new();
+        assert_eq!(
+            validator.email("email", " Me@Example.com "),
+            "me@example.com"
+        );
+        validator.password("password", "correct horse");
+        assert!(validator.finish().is_ok());
+
+        let mut validator = Validator::new();
+        validator.email("email", "  ");
+        validator.email("email", "example.com");
+        validator.password("password", "short");
+        match validator.finish() {
+            Err(ApiError::InvalidFields(errors)) => assert_eq!(errors.len(), 3),
+            _ => panic!("email and password should be invalid"),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// views are not written by diesel print-schema, they are declared here instead.
+use crate::schema::saq_wines;
+
+table! {
+    wine_matches (user_id, saq_wine_id) {
+        user_id -> Nullable<Int4>,
+        saq_wine_id -> Int4,
+        rating -> Int4,
+        wine_recommendation_ids -> Array<Int4>,
+    }
+}
+
+joinable!(wine_matches -> saq_wines (saq_wine_id));
+
+allow_tables_to_appear_in_same_query!(saq_wines, wine_matches);